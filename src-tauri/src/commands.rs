@@ -1,47 +1,66 @@
-/// Initialize the knowledge base system
-#[tauri::command]
-pub async fn initialize_knowledge_base(state: State<'_, AppState>) -> Result<String, String> {
-    let kb_manager_lock = state.knowledge_base_manager.read().await;
-    if let Some(ref kb_manager) = *kb_manager_lock {
-        match kb_manager.initialize().await {
-            Ok(()) => Ok("Knowledge base initialized successfully".to_string()),
-            Err(e) => Err(format!("Failed to initialize knowledge base: {}", e)),
-        }
-    } else {
-        Err("Knowledge base manager not configured".to_string())
-    }
-}
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
 use std::time::Instant;
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use crate::types::{Model, GenerationConfig, GenerationProgress, GenerationTask, DatasetEntry};
+use crate::types::{Model, ModelProvider, GenerationConfig, GenerationProgress, GenerationTask, DatasetEntry, OllamaConnectionStatus, GenerationBatchDiscardedEvent, GenerationEntryEvent, ModelWeight, RunSummary, GENERATION_BATCH_DISCARDED_EVENT, GENERATION_ENTRY_EVENT};
 use crate::state::AppState;
 use crate::models::ModelManager;
-use crate::dataset::DatasetGenerator;
-use crate::dataset_concurrent::{ConcurrentDatasetGenerator, ConcurrentGenerationConfig, ProgressUpdate};
+use crate::dataset::{matches_format, usable_entries};
+use crate::dataset_concurrent::{derive_seed, parse_generated_entries, ConcurrentDatasetGenerator, ConcurrentGenerationConfig, GenerationOutcome, ProgressUpdate, StreamEvent};
+use crate::llm_provider::{create_provider, CompletionRequest, OllamaProvider};
+use crate::response_cache::{CacheStats, ResponseCache};
+use crate::model_mix::{assign_batches, resolve_mix};
+use crate::ollama_pool::OllamaPool;
+use crate::quarantine::QuarantinedResponse;
+use crate::request_archive::{self, ArchiveSettings, ArchivedCall, RequestArchive};
+use crate::rate_limiter::RateLimits;
+use crate::structured_output::fit_batch_size;
+use crate::settings::{normalize_ollama_host, OpenAICompatibleEndpoint, ProviderSettings};
+use crate::usage::{ModelPricing, PriceTable};
 use crate::knowledge_base::{KnowledgeBaseManager, KnowledgeBaseConfig, KnowledgeBaseStats, ImprovementSuggestion};
 use crate::vector_db::{CollectionInfo, SearchResult, QueryRequest};
 
+/// Upper bound when probing how many requests an Ollama server runs in parallel
+const MAX_PROBED_OLLAMA_PARALLELISM: usize = 8;
+
 #[tauri::command]
 pub async fn discover_models(state: State<'_, AppState>) -> Result<Vec<Model>, String> {
     let mut all_models = Vec::new();
     
+    let provider_settings = state.provider_settings.read().await.clone();
+    
     // Discover Ollama models
-    match ModelManager::discover_ollama_models().await {
+    match ModelManager::discover_ollama_models(provider_settings.ollama_base_url()).await {
         Ok(mut ollama_models) => all_models.append(&mut ollama_models),
         Err(e) => println!("Warning: Could not discover Ollama models: {}", e),
     }
     
     // Add OpenAI models
-    match ModelManager::get_openai_models().await {
+    match ModelManager::get_openai_models(&provider_settings).await {
         Ok(mut openai_models) => all_models.append(&mut openai_models),
         Err(e) => println!("Warning: Could not get OpenAI models: {}", e),
     }
     
+    // Add Anthropic models when an API key is configured
+    if provider_settings.api_key(&ModelProvider::Anthropic).is_some() {
+        match ModelManager::discover_anthropic_models(&provider_settings).await {
+            Ok(mut anthropic_models) => all_models.append(&mut anthropic_models),
+            Err(e) => tracing::warn!("Could not get Anthropic models: {}", e),
+        }
+    }
+    
+    // Discover models on configured OpenAI-compatible endpoints
+    for endpoint in &provider_settings.openai_compatible_endpoints {
+        match ModelManager::discover_openai_compatible_models(endpoint).await {
+            Ok(mut endpoint_models) => all_models.append(&mut endpoint_models),
+            Err(e) => tracing::warn!("Could not discover models on endpoint '{}': {}", endpoint.name, e),
+        }
+    }
+    
     // Update state
     let mut models = state.models.write().await;
     *models = all_models.clone();
@@ -52,8 +71,11 @@ pub async fn discover_models(state: State<'_, AppState>) -> Result<Vec<Model>, S
 #[tauri::command]
 pub async fn start_generation(
     config: GenerationConfig,
+    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
+    config.sampling.validate().map_err(|e| e.to_string())?;
+
     // Generate unique ID for this generation session
     let generation_id = Uuid::new_v4().to_string();
     
@@ -75,6 +97,7 @@ pub async fn start_generation(
         entries_per_second: 0.0,
         errors_count: 0,
         retries_count: 0,
+        ..Default::default()
     };
     
     // Create cancellation token for this generation
@@ -92,11 +115,14 @@ pub async fn start_generation(
         active_generations: state.active_generations.clone(),
         knowledge_base_manager: state.knowledge_base_manager.clone(),
         chromadb_server: state.chromadb_server.clone(),
+        provider_settings: state.provider_settings.clone(),
+        run_summaries: state.run_summaries.clone(),
+        quarantine: state.quarantine.clone(),
     });
     
     let state_for_error = state_clone.clone();
     tokio::spawn(async move {
-        if let Err(e) = run_concurrent_generation_process(state_clone, generation_id, cancellation_token).await {
+        if let Err(e) = run_concurrent_generation_process(state_clone, app, generation_id, cancellation_token).await {
             tracing::error!("Generation error: {}", e);
             
             // Update status to error
@@ -173,14 +199,7 @@ pub async fn export_dataset(state: State<'_, AppState>) -> Result<String, String
 
     // Validate entries for format
     let filtered: Vec<&DatasetEntry> = dataset.iter().filter(|entry| {
-        match format {
-            Some(crate::types::DatasetFormat::RetrievalEmbedding) => {
-                let obj = &entry.data;
-                obj.get("query").is_some() && obj.get("positive_passage").is_some() && obj.get("negative_passages").is_some()
-            }
-            // ...add other format checks as needed...
-            _ => true
-        }
+        format.is_none_or(|format| matches_format(&entry.data, format))
     }).collect();
     
     if filtered.len() != dataset.len() {
@@ -190,14 +209,14 @@ pub async fn export_dataset(state: State<'_, AppState>) -> Result<String, String
     // Deduplicate entries
     let mut seen = std::collections::HashSet::new();
     let deduped: Vec<&DatasetEntry> = filtered.into_iter().filter(|entry| {
-        let s = format!("{:?}", entry.data);
+        let s = entry.data.to_string();
         seen.insert(s)
     }).collect();
     
     // Generate JSONL format - one JSON object per line
     let mut jsonl_lines = Vec::new();
     for entry in deduped.iter() {
-        let json_line = serde_json::to_string(entry)
+        let json_line = serde_json::to_string(&entry.data)
             .map_err(|e| {
                 tracing::error!("Failed to serialize dataset entry: {}", e);
                 format!("Failed to serialize dataset entry: {}", e)
@@ -227,11 +246,10 @@ pub async fn debug_dataset_state(state: State<'_, AppState>) -> Result<String, S
 }
 
 #[tauri::command]
-pub async fn improve_prompt(prompt: String) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .map_err(|_| "OPENAI_API_KEY not found in environment. Please set it to use prompt improvement.".to_string())?;
+pub async fn improve_prompt(prompt: String, state: State<'_, AppState>) -> Result<String, String> {
+    let settings = state.provider_settings.read().await.clone();
+    let provider = create_provider(&crate::types::ModelProvider::OpenAI, None, &settings, reqwest::Client::new())
+        .map_err(|e| e.to_string())?;
     
     let system_prompt = "You are an expert at creating fine-tuning objectives for AI models. Your task is to improve and refine user-provided fine-tuning goals to make them more specific, structured, and effective for generating high-quality training datasets.
 
@@ -246,48 +264,18 @@ Return ONLY the improved fine-tuning goal without any preamble, explanation, or
 
     let user_prompt = format!("Improve this fine-tuning goal to make it more structured, specific, and effective for dataset generation:\n\n{}", prompt);
     
-    let request_body = serde_json::json!({
-        "model": "gpt-4.1-nano",
-        "messages": [
-            {
-                "role": "system",
-                "content": system_prompt
-            },
-            {
-                "role": "user", 
-                "content": user_prompt
-            }
-        ],
-        "max_tokens": 1000,
-        "temperature": 0.7
-    });
+    let request = CompletionRequest::new("gpt-4.1-nano", user_prompt)
+        .with_system(system_prompt)
+        .with_max_tokens(1000)
+        .with_temperature(0.7);
     
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
+    let response = provider.chat(&request).await
         .map_err(|e| format!("Failed to call OpenAI API: {}", e))?;
     
-    if response.status().is_success() {
-        let result: serde_json::Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
-        
-        let improved_prompt = result["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("Failed to extract improved prompt")
-            .to_string();
-        
-        // Clean up the response to remove any preamble text
-        let cleaned_prompt = clean_improved_prompt(&improved_prompt);
-        
-        Ok(cleaned_prompt)
-    } else {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        Err(format!("OpenAI API error: {}", error_text))
-    }
+    // Clean up the response to remove any preamble text
+    let cleaned_prompt = clean_improved_prompt(&response.text);
+    
+    Ok(cleaned_prompt)
 }
 
 fn clean_improved_prompt(prompt: &str) -> String {
@@ -371,25 +359,28 @@ pub async fn generate_use_case_suggestions(
         _ => "general AI training tasks"
     };
     
-    let suggestions = match &selected_model.provider {
-        crate::types::ModelProvider::Ollama => {
-            generate_ollama_suggestions(&model_id, &domain_text, &format, format_description).await?
-        },
-        crate::types::ModelProvider::OpenAI => {
-            generate_openai_suggestions(&model_id, &domain_text, &format, format_description).await?
-        }
-    };
+    let settings = state.provider_settings.read().await.clone();
+    let suggestions = generate_model_suggestions(
+        selected_model,
+        &settings,
+        &domain_text,
+        &format,
+        format_description,
+    ).await?;
     
     Ok(suggestions)
 }
 
-async fn generate_ollama_suggestions(
-    model_id: &str,
+async fn generate_model_suggestions(
+    model: &Model,
+    settings: &crate::settings::ProviderSettings,
     domain_context: &str,
     format: &str,
     format_description: &str
 ) -> Result<Vec<String>, String> {
-    let client = reqwest::Client::new();
+    let provider = &model.provider;
+    let llm = create_provider(provider, model.endpoint.as_deref(), settings, reqwest::Client::new())
+        .map_err(|e| e.to_string())?;
     
     let prompt = format!(
         "Generate exactly 5 specific fine-tuning goals for {} format in the {} domain.
@@ -407,97 +398,17 @@ Domain: {}",
         format, domain_context, format_description, domain_context
     );
     
-    let request_body = serde_json::json!({
-        "model": model_id,
-        "prompt": prompt,
-        "stream": false
-    });
+    let request = CompletionRequest::new(model.api_model_id(), prompt).with_temperature(0.7);
     
-    let response = client
-        .post("http://localhost:11434/api/generate")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+    let response = llm.complete(&request).await
+        .map_err(|e| format!("Failed to generate suggestions from {:?}: {}", provider, e))?;
     
-    if response.status().is_success() {
-        let result: serde_json::Value = response.json().await
-            .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
-        
-        let generated_text = result["response"].as_str().unwrap_or("");
-        let suggestions = parse_suggestions(generated_text);
-        
-        if suggestions.is_empty() {
-            Ok(get_fallback_suggestions(format, domain_context))
-        } else {
-            Ok(suggestions)
-        }
-    } else {
-        Err("Failed to generate suggestions from Ollama".to_string())
-    }
-}
-
-async fn generate_openai_suggestions(
-    model_id: &str,
-    domain_context: &str,
-    format: &str,
-    format_description: &str
-) -> Result<Vec<String>, String> {
-    let client = reqwest::Client::new();
+    let suggestions = parse_suggestions(&response.text);
     
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .map_err(|_| "OPENAI_API_KEY not found in environment. Please set it to use suggestions.".to_string())?;
-    
-    let prompt = format!(
-        "Generate exactly 5 specific fine-tuning goals for {} format in the {} domain.
-
-Format: {}
-
-Requirements:
-- Each goal should be 1-2 sentences
-- Focus on practical, actionable objectives
-- Be specific to the domain and format
-- Return only the 5 goals, numbered 1-5
-- No additional text or explanations
-
-Domain: {}",
-        format, domain_context, format_description, domain_context
-    );
-    
-    let request_body = serde_json::json!({
-        "model": model_id,
-        "messages": [
-            {
-                "role": "user",
-                "content": prompt
-            }
-        ],
-        "temperature": 0.7
-    });
-    
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to connect to OpenAI: {}", e))?;
-    
-    if response.status().is_success() {
-        let result: serde_json::Value = response.json().await
-            .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
-        
-        let generated_text = result["choices"][0]["message"]["content"].as_str().unwrap_or("");
-        let suggestions = parse_suggestions(generated_text);
-        
-        if suggestions.is_empty() {
-            Ok(get_fallback_suggestions(format, domain_context))
-        } else {
-            Ok(suggestions)
-        }
+    if suggestions.is_empty() {
+        Ok(get_fallback_suggestions(format, domain_context))
     } else {
-        Err("Failed to generate suggestions from OpenAI".to_string())
+        Ok(suggestions)
     }
 }
 
@@ -566,6 +477,7 @@ fn get_fallback_suggestions(format: &str, domain_context: &str) -> Vec<String> {
 
 async fn run_concurrent_generation_process(
     state: Arc<AppState>,
+    app: AppHandle,
     generation_id: String,
     cancellation_token: CancellationToken,
 ) -> anyhow::Result<()> {
@@ -574,68 +486,148 @@ async fn run_concurrent_generation_process(
         config_guard.as_ref().unwrap().clone()
     };
     
+    let model_mix = if config.model_mix.is_empty() {
+        vec![ModelWeight { model_id: config.selected_model.clone(), weight: 1.0 }]
+    } else {
+        config.model_mix.clone()
+    };
     let models = state.models.read().await;
-    let selected_model = models.iter()
-        .find(|m| m.id == config.selected_model)
-        .ok_or_else(|| anyhow::anyhow!("Selected model not found"))?
-        .clone();
+    let model_mix = resolve_mix(&model_mix, &models)?;
+    let fallback_models = config.fallback_models
+        .iter()
+        .map(|model_id| {
+            models.iter()
+                .find(|model| &model.id == model_id)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Fallback model {} not found", model_id))
+        })
+        .collect::<anyhow::Result<Vec<Model>>>()?;
     drop(models);
+    let selected_model = model_mix[0].0.clone();
+    let run_models: Vec<&str> = model_mix.iter().map(|(model, _)| model.id.as_str()).collect();
+    let run_models = run_models.join(", ");
+    
+    let provider_settings = state.provider_settings.read().await.clone();
+    let started = Instant::now();
+    
+    if config.budget.max_cost_usd.is_some() {
+        let prices = PriceTable::new(provider_settings.model_pricing.clone());
+        for (model, _) in &model_mix {
+            if prices.price(&model.provider, model.api_model_id()).is_none() {
+                tracing::warn!("No price known for {}, the cost budget cannot be enforced", model.id);
+            }
+        }
+    }
+    
+    let archive = provider_settings.request_archive.enabled
+        .then(|| RequestArchive::for_run(&generation_id, &provider_settings));
     
     // Use different generation approaches based on provider
     let generation_result = match selected_model.provider {
-        crate::types::ModelProvider::Ollama => {
-            // Use simple sequential generation for Ollama (more reliable)
-            tracing::info!("Using sequential generation for Ollama model");
-            run_sequential_ollama_generation(state.clone(), config.clone(), selected_model.clone(), cancellation_token.clone()).await
-        }
-        crate::types::ModelProvider::OpenAI => {
-            // Use concurrent generation for OpenAI (better performance)
-            tracing::info!("Using concurrent generation for OpenAI model");
+        crate::types::ModelProvider::Ollama if model_mix.len() == 1 && provider_settings.ollama_hosts.len() > 1 => {
+            // Spread batches over every configured Ollama server that has the model
+            let pool = OllamaPool::connect(
+                reqwest::Client::new(),
+                &provider_settings.ollama_hosts,
+                selected_model.api_model_id(),
+            ).await?;
+            tracing::info!("Using {} Ollama hosts for generation", pool.len());
             
-            // Prepare generation tasks for OpenAI
-            let total_batches = (config.target_entries + config.batch_size - 1) / config.batch_size;
-            let mut tasks = Vec::new();
+            // Every host runs as many batches as it has parallel slots
+            let probes = pool.base_urls().map(|base_url| {
+                ollama_parallelism(&provider_settings, base_url, selected_model.api_model_id(), &cancellation_token)
+            });
+            let parallelism: usize = futures::future::join_all(probes).await.into_iter().sum();
+            let generation_config = ConcurrentGenerationConfig {
+                initial_concurrent_batches: parallelism,
+                max_concurrent_batches: parallelism,
+                max_concurrent_requests_per_batch: 1,
+                ollama_rate_limits: RateLimits::new(900, None),
+                openai_rate_limits: provider_settings.hosted_rate_limits.unwrap_or(RateLimits::new(4800, None)),
+                max_retries: 3,
+                retry_delay: std::time::Duration::from_millis(500),
+                max_retry_delay: std::time::Duration::from_secs(20),
+                request_timeout: std::time::Duration::from_secs(300),
+                dataset_format: config.format.clone(),
+                provider_settings,
+                budget: config.budget.clone(),
+                fallback_models: fallback_models.clone(),
+                use_response_cache: !config.bypass_response_cache,
+                max_top_up_rounds: 3,
+            };
             
-            for batch_id in 0..total_batches {
-                let remaining_entries = config.target_entries.saturating_sub(batch_id * config.batch_size);
-                let entries_to_generate = remaining_entries.min(config.batch_size);
-                
-                let context = if batch_id == 0 {
-                    "This is the first batch of the dataset.".to_string()
-                } else {
-                    format!("Previous batches completed: {}. Current progress: {}/{} total entries.", 
-                           batch_id, batch_id * config.batch_size, config.target_entries)
-                };
-                
-                tasks.push(GenerationTask {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    batch_id,
-                    entries_to_generate,
-                    model_id: selected_model.id.clone(),
-                    provider: selected_model.provider.clone(),
-                    goal: config.fine_tuning_goal.clone(),
-                    context,
-                });
+            let mut generator = ConcurrentDatasetGenerator::new(generation_config)
+                .with_provider(Arc::new(pool))
+                .with_quarantine(state.quarantine.clone())
+                .with_generation_id(generation_id.clone());
+            if let Some(archive) = &archive {
+                generator = generator.with_archive(archive.clone());
+            }
+            if config.stream {
+                generator = generator.with_entry_stream(forward_streamed_entries(app.clone(), generation_id.clone()));
             }
+            let tasks = build_generation_tasks(&config, &model_mix);
+            run_concurrent_provider_generation(generator, tasks, state.clone(), config.clone(), cancellation_token.clone()).await
+        }
+        _ => {
+            tracing::info!("Using concurrent generation for {}", run_models);
+            let local_only = model_mix
+                .iter()
+                .map(|(model, _)| model)
+                .chain(&fallback_models)
+                .all(|model| model.provider == crate::types::ModelProvider::Ollama);
+            let ollama_parallelism = if local_only {
+                Some(ollama_parallelism(
+                    &provider_settings,
+                    provider_settings.ollama_base_url(),
+                    selected_model.api_model_id(),
+                    &cancellation_token,
+                ).await)
+            } else {
+                None
+            };
             
-            let generation_config = ConcurrentGenerationConfig {
-                max_concurrent_batches: 6,
+            let mut generation_config = ConcurrentGenerationConfig {
+                initial_concurrent_batches: 4,
+                max_concurrent_batches: 16,
                 max_concurrent_requests_per_batch: 4,
-                ollama_requests_per_second: 15,
-                openai_requests_per_second: 80,
+                ollama_rate_limits: RateLimits::new(900, None),
+                openai_rate_limits: provider_settings.hosted_rate_limits.unwrap_or(RateLimits::new(4800, None)),
                 max_retries: 3,
                 retry_delay: std::time::Duration::from_millis(500),
+                max_retry_delay: std::time::Duration::from_secs(20),
                 request_timeout: std::time::Duration::from_secs(45),
                 dataset_format: config.format.clone(),
+                provider_settings,
+                budget: config.budget.clone(),
+                fallback_models: fallback_models.clone(),
+                use_response_cache: !config.bypass_response_cache,
+                max_top_up_rounds: 3,
             };
+            // A local server runs one batch per parallel slot; more would only queue behind them
+            if let Some(parallelism) = ollama_parallelism {
+                generation_config.initial_concurrent_batches = parallelism;
+                generation_config.max_concurrent_batches = parallelism;
+                generation_config.max_concurrent_requests_per_batch = 1;
+                generation_config.request_timeout = std::time::Duration::from_secs(300);
+            }
             
-            let generator = ConcurrentDatasetGenerator::new(generation_config);
-            run_concurrent_openai_generation(generator, tasks, state.clone(), config.clone(), cancellation_token.clone()).await
+            let mut generator = ConcurrentDatasetGenerator::new(generation_config)
+                .with_quarantine(state.quarantine.clone())
+                .with_generation_id(generation_id.clone());
+            if let Some(archive) = &archive {
+                generator = generator.with_archive(archive.clone());
+            }
+            if config.stream {
+                generator = generator.with_entry_stream(forward_streamed_entries(app.clone(), generation_id.clone()));
+            }
+            let tasks = build_generation_tasks(&config, &model_mix);
+            run_concurrent_provider_generation(generator, tasks, state.clone(), config.clone(), cancellation_token.clone()).await
         }
     };
     
     match generation_result {
-        Ok(all_entries) => {
+        Ok(GenerationOutcome { entries: all_entries, budget_exhausted }) => {
             tracing::info!("Generation process returned {} entries", all_entries.len());
             
             // Update dataset in state
@@ -648,12 +640,17 @@ async fn run_concurrent_generation_process(
             // Final progress update
             {
                 let mut progress = state.progress.write().await;
-                progress.status = "completed".to_string();
+                progress.status = if budget_exhausted.is_some() {
+                    "budget_exhausted".to_string()
+                } else {
+                    "completed".to_string()
+                };
+                progress.budget_exhausted = budget_exhausted;
                 progress.estimated_completion = "Finished".to_string();
-                progress.entries_generated = config.target_entries;
-                let total_batches = (config.target_entries + config.batch_size - 1) / config.batch_size;
-                progress.current_batch = total_batches;
+                progress.entries_generated = all_entries.len();
+                progress.current_batch = progress.total_batches;
             }
+            record_run_summary(&state, &generation_id, &run_models, config.seed, started).await;
             
             // Clean up active generation
             {
@@ -661,7 +658,11 @@ async fn run_concurrent_generation_process(
                 active_generations.remove(&generation_id);
             }
             
-            tracing::info!("Generation completed successfully with {} entries", config.target_entries);
+            if all_entries.len() < config.target_entries {
+                tracing::warn!("Generation finished {} entries short of the {} requested",
+                             config.target_entries - all_entries.len(), config.target_entries);
+            }
+            tracing::info!("Generation completed with {} entries", all_entries.len());
             Ok(())
         }
         Err(e) => {
@@ -674,6 +675,7 @@ async fn run_concurrent_generation_process(
                     format!("error: {}", e)
                 };
             }
+            record_run_summary(&state, &generation_id, &run_models, config.seed, started).await;
             
             // Clean up active generation
             {
@@ -686,69 +688,167 @@ async fn run_concurrent_generation_process(
     }
 }
 
-async fn run_sequential_ollama_generation(
-    state: Arc<AppState>, 
-    config: GenerationConfig, 
-    selected_model: Model,
-    cancellation_token: CancellationToken
-) -> anyhow::Result<Vec<DatasetEntry>> {
-    
-    let mut all_entries = Vec::new();
-    let total_batches = (config.target_entries + config.batch_size - 1) / config.batch_size;
-    
-    tracing::info!("Starting sequential Ollama generation: {} batches of {} entries", total_batches, config.batch_size);
+/// Keep the final progress and token usage of a run for the run summary
+async fn record_run_summary(state: &AppState, generation_id: &str, model: &str, seed: Option<u64>, started: Instant) {
+    let progress = state.progress.read().await;
+    let summary = RunSummary {
+        generation_id: generation_id.to_string(),
+        model: model.to_string(),
+        status: progress.status.clone(),
+        entries_generated: progress.entries_generated,
+        duration_secs: started.elapsed().as_secs_f64(),
+        usage: progress.usage.clone(),
+        seed,
+        system_fingerprints: progress.system_fingerprints.clone(),
+    };
+    drop(progress);
+
+    tracing::info!(
+        "Run {} used {} prompt + {} completion tokens, estimated cost: {}",
+        generation_id,
+        summary.usage.prompt_tokens,
+        summary.usage.completion_tokens,
+        summary.usage.estimated_cost_usd.map_or("unknown".to_string(), |cost| format!("${:.4}", cost)),
+    );
+    state.run_summaries.write().await.insert(generation_id.to_string(), summary);
+}
+
+/// Split a generation run into one task per batch, spreading the batches over the model mix
+fn build_generation_tasks(config: &GenerationConfig, model_mix: &[(Model, f32)]) -> Vec<GenerationTask> {
+    // Shrink batches until they fit the completion limit of every model in the mix
+    let batch_size = model_mix
+        .iter()
+        .filter_map(|(model, _)| completion_token_limit(model, config.sampling.max_tokens))
+        .map(|limit| fit_batch_size(config.batch_size, limit, &config.format))
+        .min()
+        .unwrap_or(config.batch_size)
+        .max(1);
+    if batch_size < config.batch_size {
+        tracing::warn!("Batches of {} entries do not fit the models' context windows, using {} per batch",
+                     config.batch_size, batch_size);
+    }
+    let total_batches = config.target_entries.div_ceil(batch_size);
+    let weights: Vec<f32> = model_mix.iter().map(|(_, weight)| *weight).collect();
+    let assignment = assign_batches(&weights, total_batches);
+    let mut tasks = Vec::new();
     
-    for batch_num in 0..total_batches {
-        if cancellation_token.is_cancelled() {
-            return Err(anyhow::anyhow!("Generation cancelled"));
-        }
+    for (batch_id, &model_index) in assignment.iter().enumerate() {
+        let model = &model_mix[model_index].0;
+        let remaining_entries = config.target_entries.saturating_sub(batch_id * batch_size);
+        let entries_to_generate = remaining_entries.min(batch_size);
         
-        let remaining_entries = config.target_entries - all_entries.len();
-        let current_batch_size = remaining_entries.min(config.batch_size);
+        let context = if batch_id == 0 {
+            "This is the first batch of the dataset.".to_string()
+        } else {
+            format!("Previous batches completed: {}. Current progress: {}/{} total entries.", 
+                   batch_id, batch_id * batch_size, config.target_entries)
+        };
         
-        tracing::info!("Processing batch {}/{} with {} entries", batch_num + 1, total_batches, current_batch_size);
+        let mut sampling = config.sampling.for_batch(batch_id, total_batches);
+        sampling.max_tokens = completion_token_limit(model, sampling.max_tokens);
         
-        // Update progress
-        {
-            let mut progress = state.progress.write().await;
-            progress.current_batch = batch_num + 1;
-            progress.status = format!("Generating batch {}/{}", batch_num + 1, total_batches);
+        tasks.push(GenerationTask {
+            id: uuid::Uuid::new_v4().to_string(),
+            batch_id,
+            entries_to_generate,
+            model_id: model.api_model_id().to_string(),
+            provider: model.provider.clone(),
+            endpoint: model.endpoint.clone(),
+            goal: config.fine_tuning_goal.clone(),
+            domain_context: config.domain_context.clone(),
+            context,
+            seed: config.seed.map(|seed| derive_seed(seed, batch_id as u64)),
+            sampling,
+        });
+    }
+    
+    tasks
+}
+
+/// Completion tokens a batch may use: `max_tokens`, capped at half the model's context window
+///
+/// The other half is left for the prompt.
+fn completion_token_limit(model: &Model, max_tokens: Option<u32>) -> Option<u32> {
+    let context_limit = model.details.context_length.map(|context_length| u32::try_from(context_length / 2).unwrap_or(u32::MAX));
+    match (context_limit, max_tokens) {
+        (Some(context_limit), Some(max_tokens)) => Some(context_limit.min(max_tokens)),
+        (context_limit, max_tokens) => context_limit.or(max_tokens),
+    }
+}
+
+/// Parallel request slots of an Ollama server for a model: the configured value, else a probe
+///
+/// Probe results are kept for the session, since probing costs a few generations.
+async fn ollama_parallelism(
+    settings: &ProviderSettings,
+    base_url: &str,
+    model: &str,
+    cancellation_token: &CancellationToken,
+) -> usize {
+    static PROBED: OnceLock<std::sync::Mutex<HashMap<(String, String), usize>>> = OnceLock::new();
+
+    if let Some(parallelism) = settings.ollama_num_parallel {
+        return parallelism.max(1);
+    }
+    let key = (base_url.to_string(), model.to_string());
+    if let Some(&parallelism) = PROBED.get_or_init(Default::default).lock().unwrap().get(&key) {
+        return parallelism;
+    }
+
+    let provider = OllamaProvider::new(reqwest::Client::new(), base_url);
+    let probe = tokio::select! {
+        probe = provider.probe_parallelism(model, MAX_PROBED_OLLAMA_PARALLELISM) => probe,
+        _ = cancellation_token.cancelled() => return 1,
+    };
+    match probe {
+        Ok(parallelism) => {
+            tracing::info!("Ollama at {} runs {} parallel requests for {}", base_url, parallelism, model);
+            PROBED.get_or_init(Default::default).lock().unwrap().insert(key, parallelism);
+            parallelism
         }
-        
-        // Generate batch using original DatasetGenerator
-        let batch_entries = DatasetGenerator::generate_batch(
-            &selected_model.id,
-            &selected_model.provider,
-            &config.fine_tuning_goal,
-            &config.format,
-            current_batch_size,
-            &all_entries,
-        ).await?;
-        
-        tracing::info!("Batch {} generated {} entries", batch_num + 1, batch_entries.len());
-        all_entries.extend(batch_entries);
-        
-        // Update progress
-        {
-            let mut progress = state.progress.write().await;
-            progress.entries_generated = all_entries.len();
+        Err(e) => {
+            tracing::warn!("Could not probe Ollama parallelism at {}, using 1: {}", base_url, e);
+            1
         }
-        
-        // Small delay to prevent overwhelming Ollama
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     }
-    
-    tracing::info!("Sequential Ollama generation completed with {} total entries", all_entries.len());
-    Ok(all_entries)
 }
 
-async fn run_concurrent_openai_generation(
+/// Push a streamed entry, or the withdrawal of a failed attempt's entries, to the frontend
+fn emit_stream_event(app: &AppHandle, generation_id: &str, event: StreamEvent) {
+    let generation_id = generation_id.to_string();
+    let result = match event {
+        StreamEvent::Entry { batch_id, attempt_id, entry } => app.emit(
+            GENERATION_ENTRY_EVENT,
+            GenerationEntryEvent { generation_id, batch_id, attempt_id, entry },
+        ),
+        StreamEvent::Discarded { batch_id, attempt_id } => app.emit(
+            GENERATION_BATCH_DISCARDED_EVENT,
+            GenerationBatchDiscardedEvent { generation_id, batch_id, attempt_id },
+        ),
+    };
+    if let Err(e) = result {
+        tracing::warn!("Failed to emit streamed generation event: {}", e);
+    }
+}
+
+/// Channel for the concurrent generator's streamed entries, forwarded as Tauri events
+fn forward_streamed_entries(app: AppHandle, generation_id: String) -> mpsc::UnboundedSender<StreamEvent> {
+    let (entry_tx, mut entry_rx) = mpsc::unbounded_channel::<StreamEvent>();
+    tokio::spawn(async move {
+        while let Some(event) = entry_rx.recv().await {
+            emit_stream_event(&app, &generation_id, event);
+        }
+    });
+    entry_tx
+}
+
+async fn run_concurrent_provider_generation(
     generator: ConcurrentDatasetGenerator,
     tasks: Vec<GenerationTask>,
     state: Arc<AppState>,
     config: GenerationConfig,
     cancellation_token: CancellationToken
-) -> anyhow::Result<Vec<DatasetEntry>> {
+) -> anyhow::Result<GenerationOutcome> {
     // Set up progress channel
     let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<ProgressUpdate>();
     
@@ -765,7 +865,21 @@ async fn run_concurrent_openai_generation(
             progress.errors_count = update.errors_count;
             progress.retries_count = update.retries_count;
             progress.concurrent_batches = update.concurrent_batches;
+            progress.total_batches = update.total_batches;
             progress.entries_per_second = update.entries_per_second;
+            progress.parse_success_rate = update.parse_success_rate;
+            if let (Some(batch_id), Some(rate)) = (update.batch_completed, update.batch_parse_success_rate) {
+                progress.batch_parse_success_rates.insert(batch_id, rate);
+            }
+            progress.usage = update.usage;
+            if let (Some(batch_id), Some(substitution)) = (update.batch_completed, update.batch_substitution) {
+                progress.model_substitutions.insert(batch_id, substitution);
+            }
+            progress.system_fingerprints.extend(update.batch_system_fingerprints);
+            if let Some(error_class) = update.error_class {
+                *progress.error_classes.entry(error_class).or_default() += 1;
+            }
+            progress.quarantined_responses = update.quarantined_responses;
             
             if let Some(completed_batch) = update.batch_completed {
                 progress.current_batch = completed_batch + 1;
@@ -783,14 +897,8 @@ async fn run_concurrent_openai_generation(
                 };
             }
             
-            // Update status
-            if update.entries_generated >= config.target_entries {
-                progress.status = "completed".to_string();
-                progress.estimated_completion = "Finished".to_string();
-                break;
-            } else {
-                progress.status = format!("Processing {} concurrent batches", update.concurrent_batches);
-            }
+            // Completion is set once the run returns, after any top-up batches
+            progress.status = format!("Processing {} concurrent batches", update.concurrent_batches);
         }
     });
     
@@ -807,13 +915,287 @@ async fn run_concurrent_openai_generation(
     generation_result
 }
 
+// ============================================================================
+// Provider Settings Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn list_openai_compatible_endpoints(state: State<'_, AppState>) -> Result<Vec<OpenAICompatibleEndpoint>, String> {
+    let settings = state.provider_settings.read().await;
+    Ok(settings.openai_compatible_endpoints.clone())
+}
+
+/// Add or replace an OpenAI-compatible endpoint (vLLM, llama.cpp server, LM Studio, LocalAI)
+#[tauri::command]
+pub async fn add_openai_compatible_endpoint(
+    name: String,
+    base_url: String,
+    api_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Model>, String> {
+    if name.trim().is_empty() || base_url.trim().is_empty() {
+        return Err("Endpoint name and base URL are required".to_string());
+    }
+    
+    let endpoint = OpenAICompatibleEndpoint {
+        name: name.trim().to_string(),
+        base_url: base_url.trim().to_string(),
+        api_key,
+    };
+    
+    // Verify the endpoint responds before saving it
+    let models = ModelManager::discover_openai_compatible_models(&endpoint).await
+        .map_err(|e| format!("Failed to reach endpoint '{}': {}", endpoint.name, e))?;
+    
+    let mut settings = state.provider_settings.write().await;
+    settings.upsert_openai_compatible_endpoint(endpoint);
+    settings.save().map_err(|e| format!("Failed to save settings: {}", e))?;
+    
+    Ok(models)
+}
+
+#[tauri::command]
+pub async fn remove_openai_compatible_endpoint(name: String, state: State<'_, AppState>) -> Result<(), String> {
+    let mut settings = state.provider_settings.write().await;
+    if !settings.remove_openai_compatible_endpoint(&name) {
+        return Err(format!("Endpoint '{}' not found", name));
+    }
+    settings.save().map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// Hosted providers with an API key, stored or from the environment; the keys themselves never leave the backend
+#[tauri::command]
+pub async fn get_configured_api_keys(state: State<'_, AppState>) -> Result<Vec<ModelProvider>, String> {
+    let settings = state.provider_settings.read().await;
+    Ok([ModelProvider::OpenAI, ModelProvider::Anthropic]
+        .into_iter()
+        .filter(|provider| settings.api_key(provider).is_some())
+        .collect())
+}
+
+/// Store the OpenAI or Anthropic API key in the OS credential store; an empty key removes it
+#[tauri::command]
+pub async fn set_provider_api_key(
+    provider: ModelProvider,
+    api_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut settings = state.provider_settings.write().await;
+    settings.set_api_key(&provider, api_key)
+        .map_err(|e| format!("Failed to store the {:?} API key: {}", provider, e))
+}
+
+#[tauri::command]
+pub async fn get_ollama_hosts(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let settings = state.provider_settings.read().await;
+    if settings.ollama_hosts.is_empty() {
+        Ok(vec![settings.ollama_base_url().to_string()])
+    } else {
+        Ok(settings.ollama_hosts.clone())
+    }
+}
+
+/// Replace the configured Ollama servers; the first host is the primary one
+#[tauri::command]
+pub async fn set_ollama_hosts(hosts: Vec<String>, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let mut settings = state.provider_settings.write().await;
+    settings.set_ollama_hosts(hosts);
+    settings.save().map_err(|e| format!("Failed to save settings: {}", e))?;
+    
+    Ok(settings.ollama_hosts.clone())
+}
+
+#[tauri::command]
+pub async fn get_pinned_openai_models(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let settings = state.provider_settings.read().await;
+    Ok(settings.pinned_openai_models.clone())
+}
+
+/// Replace the OpenAI model ids that are always offered alongside discovered models
+#[tauri::command]
+pub async fn set_pinned_openai_models(models: Vec<String>, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let mut settings = state.provider_settings.write().await;
+    settings.pinned_openai_models = models
+        .into_iter()
+        .map(|model| model.trim().to_string())
+        .filter(|model| !model.is_empty())
+        .collect();
+    settings.save().map_err(|e| format!("Failed to save settings: {}", e))?;
+    
+    Ok(settings.pinned_openai_models.clone())
+}
+
+/// Hit rate and disk usage of the provider response cache
+#[tauri::command]
+pub async fn get_response_cache_stats() -> Result<CacheStats, String> {
+    Ok(ResponseCache::shared().stats())
+}
+
+/// Delete all cached provider responses, returning how many were removed
+#[tauri::command]
+pub async fn clear_response_cache() -> Result<usize, String> {
+    ResponseCache::shared()
+        .clear()
+        .map_err(|e| format!("Failed to clear response cache: {}", e))
+}
+
+/// Responses that failed to parse, oldest first
+#[tauri::command]
+pub async fn list_quarantined_responses(state: State<'_, AppState>) -> Result<Vec<QuarantinedResponse>, String> {
+    Ok(state.quarantine.list())
+}
+
+/// Send a quarantined request again, adding the entries to the dataset if the new response parses
+///
+/// Returns the number of entries added; a response that fails again stays quarantined with the new output.
+#[tauri::command]
+pub async fn retry_quarantined_response(id: String, state: State<'_, AppState>) -> Result<usize, String> {
+    let quarantined = state.quarantine.get(&id)
+        .ok_or_else(|| format!("No quarantined response with id {}", id))?;
+    // The dataset in memory is that of the latest run, and is replaced when that run finishes
+    if state.progress.read().await.generation_id.as_deref() != Some(quarantined.generation_id.as_str()) {
+        return Err(format!("Response {} belongs to generation {}, not the current dataset", id, quarantined.generation_id));
+    }
+    if state.active_generations.read().await.contains_key(&quarantined.generation_id) {
+        return Err("Wait for the generation to finish before retrying its quarantined responses".to_string());
+    }
+    let format = state.generation_config.read().await.as_ref().map(|config| config.format.clone())
+        .ok_or("No generation configuration")?;
+    let settings = state.provider_settings.read().await.clone();
+    let llm = create_provider(&quarantined.provider, quarantined.endpoint.as_deref(), &settings, reqwest::Client::new())
+        .map_err(|e| e.to_string())?;
+    let response = llm.complete(&quarantined.request).await
+        .map_err(|e| format!("Failed to retry batch {}: {}", quarantined.batch_id, e))?;
+
+    match parse_generated_entries(&response.text) {
+        Ok(mut entries) => {
+            for entry in &mut entries {
+                entry.source_model = Some(quarantined.model_id.clone());
+            }
+            // Only entries of the right shape that the dataset does not already hold are added
+            let mut dataset = state.dataset.write().await;
+            let existing: HashSet<String> = dataset.iter().map(|entry| entry.data.to_string()).collect();
+            let new_entries: Vec<DatasetEntry> = usable_entries(entries, &format)
+                .into_iter()
+                .filter(|entry| !existing.contains(&entry.data.to_string()))
+                .collect();
+            let added = new_entries.len();
+            dataset.extend(new_entries);
+            state.quarantine.remove(&id);
+            tracing::info!("Retried quarantined response {}: added {} entries", id, added);
+            Ok(added)
+        }
+        Err(unparseable) => {
+            let error = format!("Response still failed to parse: {}", unparseable.error);
+            state.quarantine.update(&id, unparseable);
+            Err(error)
+        }
+    }
+}
+
+/// Drop a quarantined response without retrying it
+#[tauri::command]
+pub async fn discard_quarantined_response(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.quarantine.remove(&id)
+        .map(|_| ())
+        .ok_or_else(|| format!("No quarantined response with id {}", id))
+}
+
+/// Provider calls archived for a generation run, in the order they finished
+#[tauri::command]
+pub async fn get_request_archive(generation_id: String) -> Result<Vec<ArchivedCall>, String> {
+    request_archive::load(&generation_id)
+        .map_err(|e| format!("Failed to read request archive for {}: {}", generation_id, e))
+}
+
+#[tauri::command]
+pub async fn get_request_archive_settings(state: State<'_, AppState>) -> Result<ArchiveSettings, String> {
+    Ok(state.provider_settings.read().await.request_archive.clone())
+}
+
+/// Enable or disable archiving, and set its retention and redaction
+#[tauri::command]
+pub async fn set_request_archive_settings(
+    archive_settings: ArchiveSettings,
+    state: State<'_, AppState>,
+) -> Result<ArchiveSettings, String> {
+    let mut settings = state.provider_settings.write().await;
+    settings.request_archive = archive_settings;
+    settings.save().map_err(|e| format!("Failed to save settings: {}", e))?;
+    
+    Ok(settings.request_archive.clone())
+}
+
+/// Token usage and estimated cost of a finished generation run
+#[tauri::command]
+pub async fn get_run_summary(generation_id: String, state: State<'_, AppState>) -> Result<Option<RunSummary>, String> {
+    Ok(state.run_summaries.read().await.get(&generation_id).cloned())
+}
+
+/// Model prices used for cost estimates: the built-in table with configured prices applied
+#[tauri::command]
+pub async fn get_model_pricing(state: State<'_, AppState>) -> Result<BTreeMap<String, ModelPricing>, String> {
+    let settings = state.provider_settings.read().await;
+    Ok(PriceTable::new(settings.model_pricing.clone()).entries())
+}
+
+/// Parallel requests per Ollama server; None means it is probed at the start of a run
+#[tauri::command]
+pub async fn get_ollama_parallelism(state: State<'_, AppState>) -> Result<Option<usize>, String> {
+    Ok(state.provider_settings.read().await.ollama_num_parallel)
+}
+
+#[tauri::command]
+pub async fn set_ollama_parallelism(
+    parallelism: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Option<usize>, String> {
+    let mut settings = state.provider_settings.write().await;
+    settings.ollama_num_parallel = parallelism.filter(|parallelism| *parallelism > 0);
+    settings.save().map_err(|e| format!("Failed to save settings: {}", e))?;
+    
+    Ok(settings.ollama_num_parallel)
+}
+
+/// Replace the configured model prices (USD per million tokens)
+#[tauri::command]
+pub async fn set_model_pricing(
+    pricing: BTreeMap<String, ModelPricing>,
+    state: State<'_, AppState>,
+) -> Result<BTreeMap<String, ModelPricing>, String> {
+    let mut settings = state.provider_settings.write().await;
+    settings.model_pricing = pricing;
+    settings.save().map_err(|e| format!("Failed to save settings: {}", e))?;
+    
+    Ok(PriceTable::new(settings.model_pricing.clone()).entries())
+}
+
+/// Probe an Ollama server (the primary configured host by default)
+#[tauri::command]
+pub async fn check_ollama_connection(
+    base_url: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<OllamaConnectionStatus, String> {
+    let base_url = match base_url {
+        Some(host) => normalize_ollama_host(&host),
+        None => state.provider_settings.read().await.ollama_base_url().to_string(),
+    };
+    
+    Ok(ModelManager::check_ollama_connection(&base_url).await)
+}
+
 // ============================================================================
 // Knowledge Base Commands
 // ============================================================================
 
 #[tauri::command]
-pub async fn initialize_knowledge_base(state: State<'_, AppState>) -> Result<(), String> {
-    let kb_manager = KnowledgeBaseManager::new(KnowledgeBaseConfig::default());
+pub async fn initialize_knowledge_base(
+    config: Option<KnowledgeBaseConfig>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let settings = state.provider_settings.read().await.clone();
+    let kb_manager = KnowledgeBaseManager::new(config.unwrap_or_default(), &settings)
+        .map_err(|e| format!("Failed to initialize knowledge base: {}", e))?;
     
     match kb_manager.initialize().await {
         Ok(_) => {
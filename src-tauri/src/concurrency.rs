@@ -0,0 +1,153 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::retry::ErrorClass;
+
+/// Latency above this multiple of the baseline counts as congestion, so concurrency stops growing
+const LATENCY_TOLERANCE: f64 = 2.0;
+/// Share of the limit kept after an overload signal
+const DECREASE_FACTOR: f64 = 0.5;
+/// Weight of a slower sample when updating the latency baseline; faster samples replace it
+const BASELINE_DRIFT: f64 = 0.05;
+
+#[derive(Debug)]
+struct ControllerState {
+    limit: f64,
+    in_flight: usize,
+    /// Typical healthy batch latency, in seconds
+    baseline_latency: Option<f64>,
+    last_decrease: Option<Instant>,
+}
+
+/// AIMD limit on concurrent batches: grows by one per window of healthy batches,
+/// halves on timeouts, rate limits and server errors
+///
+/// Clones share state, so every batch of a run reports into the same limit.
+#[derive(Debug, Clone)]
+pub struct AdaptiveConcurrency {
+    state: Arc<Mutex<ControllerState>>,
+    released: Arc<Notify>,
+    min: usize,
+    max: usize,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(initial: usize, min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        let max = max.max(min);
+        Self {
+            state: Arc::new(Mutex::new(ControllerState {
+                limit: initial.clamp(min, max) as f64,
+                in_flight: 0,
+                baseline_latency: None,
+                last_decrease: None,
+            })),
+            released: Arc::new(Notify::new()),
+            min,
+            max,
+        }
+    }
+
+    /// Current number of batches allowed to run at once
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    /// Wait for a free slot under the current limit
+    pub async fn acquire(&self) -> ConcurrencyPermit {
+        loop {
+            // Created before checking so a release in between still wakes us
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit as usize {
+                    state.in_flight += 1;
+                    return ConcurrencyPermit { controller: self.clone() };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// A request finished; grow the limit if its latency is healthy
+    pub fn record_success(&self, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let latency = latency.as_secs_f64();
+        let baseline = match state.baseline_latency {
+            Some(baseline) if latency > baseline => baseline + (latency - baseline) * BASELINE_DRIFT,
+            _ => latency,
+        };
+        state.baseline_latency = Some(baseline);
+
+        if latency <= baseline * LATENCY_TOLERANCE {
+            let previous = state.limit as usize;
+            state.limit = (state.limit + 1.0 / state.limit).min(self.max as f64);
+            if state.limit as usize > previous {
+                tracing::debug!("Raising batch concurrency to {}", state.limit as usize);
+                self.released.notify_waiters();
+            }
+        }
+    }
+
+    /// A request failed; timeouts, rate limits and server errors halve the limit
+    ///
+    /// Decreases are spaced at least one baseline latency apart, so a burst of
+    /// failures from requests already in flight counts as a single signal.
+    pub fn record_error(&self, error_class: ErrorClass) {
+        if !matches!(error_class, ErrorClass::Timeout | ErrorClass::RateLimited | ErrorClass::ServerError) {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let cooldown = Duration::from_secs_f64(state.baseline_latency.unwrap_or(1.0));
+        if state.last_decrease.is_some_and(|last| last.elapsed() < cooldown) {
+            return;
+        }
+        state.limit = (state.limit * DECREASE_FACTOR).max(self.min as f64);
+        state.last_decrease = Some(Instant::now());
+        tracing::info!("{} error, lowering batch concurrency to {}", error_class, state.limit as usize);
+    }
+}
+
+/// A running batch's slot; released on drop
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    controller: AdaptiveConcurrency,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        self.controller.state.lock().unwrap().in_flight -= 1;
+        self.controller.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_grows_while_healthy_and_halves_on_overload() {
+        let controller = AdaptiveConcurrency::new(2, 1, 4);
+        let _first = controller.acquire().await;
+        let _second = controller.acquire().await;
+        let blocked = tokio::time::timeout(Duration::from_millis(20), controller.acquire()).await;
+        assert!(blocked.is_err());
+
+        for _ in 0..3 {
+            controller.record_success(Duration::from_millis(100));
+        }
+        assert_eq!(controller.limit(), 3);
+        let _third = tokio::time::timeout(Duration::from_millis(20), controller.acquire()).await.unwrap();
+
+        controller.record_success(Duration::from_secs(5));
+        assert_eq!(controller.limit(), 3);
+
+        controller.record_error(ErrorClass::RateLimited);
+        controller.record_error(ErrorClass::Timeout);
+        assert_eq!(controller.limit(), 1);
+        controller.record_error(ErrorClass::Auth);
+        assert_eq!(controller.limit(), 1);
+    }
+}
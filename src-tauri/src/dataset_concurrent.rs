@@ -1,41 +1,75 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use futures::stream::{FuturesUnordered, StreamExt};
-use std::sync::Mutex;
-use tokio::sync::{mpsc, RwLock, Semaphore};
+use tokio::sync::{mpsc, RwLock};
 use tokio_util::sync::CancellationToken;
 use anyhow::Result;
 use crate::types::{
-    DatasetEntry, ModelProvider, GenerationTask, BatchResult, DatasetFormat
+    DatasetEntry, Model, ModelProvider, ModelSubstitution, GenerationTask, BatchResult, DatasetFormat
 };
-use crate::prompt_template::PromptTemplateEngine;
+use crate::llm_provider::{create_provider, CompletionRequest, CompletionResponse, LlmProvider};
+use crate::response_cache::{CachedProvider, ResponseCache};
+use crate::settings::ProviderSettings;
+use crate::concurrency::AdaptiveConcurrency;
+use crate::retry::{classify, ErrorClass, RetryPolicy};
+use crate::rate_limiter::{estimate_tokens, RateLimitedError, RateLimiter, RateLimits};
+use crate::usage::{BudgetLimit, GenerationBudget, PriceTable, UsageSummary};
+use crate::structured_output::{IncrementalEntryParser, ParsedBatch, ResponseSchema};
+use crate::json_recovery::recover_entries;
+use crate::dataset::usable_entries;
+use crate::quarantine::{QuarantineStore, QuarantinedResponse, UnparseableResponse};
+use crate::request_archive::{ArchivingProvider, RequestArchive};
+use crate::prompt_template::{GenerationPrompt, PromptContext, PromptTemplateEngine};
 use crate::quality_validator::ValidationFeedback;
 
 /// Configuration for concurrent dataset generation
 #[derive(Debug, Clone)]
 pub struct ConcurrentGenerationConfig {
+    /// Batch concurrency the adaptive controller starts from
+    pub initial_concurrent_batches: usize,
+    /// Ceiling for the adaptive batch concurrency
     pub max_concurrent_batches: usize,
     pub max_concurrent_requests_per_batch: usize,
-    pub ollama_requests_per_second: u32,
-    pub openai_requests_per_second: u32,
+    /// Limits of each Ollama or OpenAI-compatible server
+    pub ollama_rate_limits: RateLimits,
+    /// Limits of each hosted API, OpenAI and Anthropic separately
+    pub openai_rate_limits: RateLimits,
     pub max_retries: usize,
+    /// First retry delay; doubles with each further retry up to `max_retry_delay`
     pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
     pub request_timeout: Duration,
     pub dataset_format: crate::types::DatasetFormat,
+    pub provider_settings: ProviderSettings,
+    pub budget: GenerationBudget,
+    /// Models tried in order for batches that fail on their own model
+    pub fallback_models: Vec<Model>,
+    /// Answer repeated requests from the on-disk response cache
+    pub use_response_cache: bool,
+    /// Rounds of extra batches scheduled when a run ends short of its target entry count
+    pub max_top_up_rounds: usize,
 }
 
 impl Default for ConcurrentGenerationConfig {
     fn default() -> Self {
         Self {
+            initial_concurrent_batches: 2,
             max_concurrent_batches: 4,
             max_concurrent_requests_per_batch: 3,
-            ollama_requests_per_second: 10,
-            openai_requests_per_second: 60, // OpenAI allows 60 requests per minute for tier 1
+            ollama_rate_limits: RateLimits::new(600, None),
+            openai_rate_limits: RateLimits::new(500, Some(200_000)), // OpenAI tier 1 limits for gpt-4o-mini
             max_retries: 3,
             retry_delay: Duration::from_millis(1000),
+            max_retry_delay: Duration::from_secs(30),
             request_timeout: Duration::from_secs(30),
             dataset_format: crate::types::DatasetFormat::Alpaca,
+            provider_settings: ProviderSettings::from_env(),
+            budget: GenerationBudget::default(),
+            fallback_models: Vec::new(),
+            use_response_cache: false,
+            max_top_up_rounds: 3,
         }
     }
 }
@@ -47,97 +81,75 @@ pub struct ProgressUpdate {
     pub entries_generated: usize,
     pub errors_count: usize,
     pub retries_count: usize,
+    /// Current limit of the adaptive concurrency controller
     pub concurrent_batches: usize,
+    /// Batches scheduled so far, including top-up batches
+    pub total_batches: usize,
     pub entries_per_second: f64,
+    /// Share of model responses that parsed, across the run so far
+    pub parse_success_rate: f32,
+    /// Parse success rate of `batch_completed`
+    pub batch_parse_success_rate: Option<f32>,
+    /// Tokens spent so far
+    pub usage: UsageSummary,
+    /// Set when `batch_completed` was produced by a fallback model
+    pub batch_substitution: Option<ModelSubstitution>,
+    /// Class of the error that failed a batch, for error updates
+    pub error_class: Option<ErrorClass>,
+    /// System fingerprints reported for `batch_completed`
+    pub batch_system_fingerprints: BTreeSet<String>,
+    /// Responses that failed to parse and were quarantined, across the run so far
+    pub quarantined_responses: usize,
 }
 
-/// Simple rate limiter for API requests
-#[derive(Debug)]
-pub struct SimpleRateLimiter {
-    last_request: Arc<Mutex<Instant>>,
-    min_interval: Duration,
+/// Entries produced by a run and why it stopped early, if it did
+#[derive(Debug, Clone)]
+pub struct GenerationOutcome {
+    pub entries: Vec<DatasetEntry>,
+    pub budget_exhausted: Option<BudgetLimit>,
 }
 
-impl SimpleRateLimiter {
-    pub fn new(requests_per_second: u32) -> Self {
-        let min_interval = Duration::from_millis(1000 / requests_per_second as u64);
-        Self {
-            last_request: Arc::new(Mutex::new(Instant::now() - min_interval)),
-            min_interval,
-        }
-    }
-
-    pub async fn wait_for_permit(&self) {
-        loop {
-            let now = Instant::now();
-            let should_wait = {
-                let mut last = self.last_request.lock().unwrap();
-                let elapsed = now.duration_since(*last);
-                if elapsed >= self.min_interval {
-                    *last = now;
-                    false
-                } else {
-                    true
-                }
-            };
-
-            if !should_wait {
-                break;
-            }
-
-            tokio::time::sleep(Duration::from_millis(10)).await;
-        }
-    }
+/// Provisional output of streaming responses, superseded by the entries the run returns
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// An entry parsed from a response that is still streaming
+    Entry { batch_id: usize, attempt_id: String, entry: DatasetEntry },
+    /// The attempt failed or will be retried, so the entries it streamed are void
+    Discarded { batch_id: usize, attempt_id: String },
 }
 
+/// Provider and endpoint a rate limiter applies to
+type RateLimiterKey = (ModelProvider, Option<String>);
+
 /// Highly optimized concurrent dataset generator with enhanced prompt system
 pub struct ConcurrentDatasetGenerator {
     config: ConcurrentGenerationConfig,
-    ollama_rate_limiter: SimpleRateLimiter,
-    openai_rate_limiter: SimpleRateLimiter,
+    /// One limiter per provider and endpoint, created on first use and shared by clones
+    rate_limiters: Arc<Mutex<HashMap<RateLimiterKey, RateLimiter>>>,
+    concurrency: AdaptiveConcurrency,
     client: reqwest::Client,
-    prompt_engine: PromptTemplateEngine,
+    /// Shared by clones, so feedback reaches the prompts of every batch
+    prompt_engine: Arc<RwLock<PromptTemplateEngine>>,
     validation_feedback_history: Arc<RwLock<Vec<ValidationFeedback>>>,
+    llm: Option<Arc<dyn LlmProvider>>,
+    entry_tx: Option<mpsc::UnboundedSender<StreamEvent>>,
+    quarantine: QuarantineStore,
+    archive: Option<Arc<RequestArchive>>,
+    /// Used when `use_response_cache` is set
+    response_cache: Arc<ResponseCache>,
+    /// Run the generated output belongs to, recorded with quarantined responses
+    generation_id: String,
+    /// Tokens reported by every response of the current run, including failed and retried attempts
+    usage: Arc<RwLock<UsageSummary>>,
+    prices: Arc<PriceTable>,
+    /// Responses received in the current run, and how many of them were quarantined
+    responses: Arc<AtomicUsize>,
+    quarantined_responses: Arc<AtomicUsize>,
 }
 
 impl ConcurrentDatasetGenerator {
-    /// Parse generated entries from model output
-    fn parse_generated_entries(&self, text: &str, expected_count: usize) -> Result<Vec<DatasetEntry>, anyhow::Error> {
-        tracing::info!("Parsing generated entries, expected count: {}", expected_count);
-        // Try to extract JSON from the response (handle cases where there's extra text)
-        let json_text = if let Some(start) = text.find('[') {
-            if let Some(end) = text.rfind(']') {
-                &text[start..=end]
-            } else {
-                text
-            }
-        } else {
-            text
-        };
-
-        tracing::debug!("Extracted JSON text: {}", json_text);
-
-        match serde_json::from_str::<Vec<DatasetEntry>>(json_text) {
-            Ok(entries) => {
-                tracing::info!("Successfully parsed {} entries from JSON", entries.len());
-                if entries.is_empty() {
-                    tracing::warn!("Parsed entries is empty, generating fallback");
-                    Ok(self.generate_fallback_entries(expected_count))
-                } else {
-                    Ok(entries)
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Failed to parse generated JSON: {}, using fallback entries", e);
-                tracing::debug!("Failed JSON content: {}", json_text);
-                Ok(self.generate_fallback_entries(expected_count))
-            }
-        }
-    }
     pub fn new(config: ConcurrentGenerationConfig) -> Self {
-        // Create rate limiters for different providers
-        let ollama_rate_limiter = SimpleRateLimiter::new(config.ollama_requests_per_second);
-        let openai_rate_limiter = SimpleRateLimiter::new(config.openai_requests_per_second);
+        let concurrency = AdaptiveConcurrency::new(config.initial_concurrent_batches, 1, config.max_concurrent_batches);
 
         // Create optimized HTTP client with connection pooling
         let client = reqwest::Client::builder()
@@ -149,17 +161,64 @@ impl ConcurrentDatasetGenerator {
 
         // Initialize prompt template engine
         let prompt_engine = PromptTemplateEngine::new();
+        let prices = Arc::new(PriceTable::new(config.provider_settings.model_pricing.clone()));
 
         Self {
             config,
-            ollama_rate_limiter,
-            openai_rate_limiter,
+            rate_limiters: Arc::new(Mutex::new(HashMap::new())),
+            concurrency,
             client,
-            prompt_engine,
+            prompt_engine: Arc::new(RwLock::new(prompt_engine)),
             validation_feedback_history: Arc::new(RwLock::new(Vec::new())),
+            llm: None,
+            entry_tx: None,
+            quarantine: QuarantineStore::new(),
+            archive: None,
+            response_cache: ResponseCache::shared(),
+            generation_id: uuid::Uuid::new_v4().to_string(),
+            usage: Arc::new(RwLock::new(UsageSummary::default())),
+            prices,
+            responses: Arc::new(AtomicUsize::new(0)),
+            quarantined_responses: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// Stream responses and receive entries as they are parsed, and the attempts whose entries are void
+    pub fn with_entry_stream(mut self, entry_tx: mpsc::UnboundedSender<StreamEvent>) -> Self {
+        self.entry_tx = Some(entry_tx);
+        self
+    }
+
+    /// Keep unparseable responses in this store
+    pub fn with_quarantine(mut self, quarantine: QuarantineStore) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    /// Identify the run, instead of a generated id
+    pub fn with_generation_id(mut self, generation_id: impl Into<String>) -> Self {
+        self.generation_id = generation_id.into();
+        self
+    }
+
+    /// Record every provider call in this archive
+    pub fn with_archive(mut self, archive: Arc<RequestArchive>) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// Answer repeated requests from this cache instead of the app-wide one
+    pub fn with_response_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.response_cache = cache;
+        self
+    }
+
+    /// Send every task through this provider instead of creating one per task
+    pub fn with_provider(mut self, llm: Arc<dyn LlmProvider>) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
     /// Update the generator with validation feedback for continuous improvement
     pub async fn update_with_feedback(
         &mut self,
@@ -179,7 +238,7 @@ impl ConcurrentDatasetGenerator {
         }
 
         // Update prompt templates based on feedback
-        self.prompt_engine.update_template_with_feedback(format, &feedback, batch_quality_score)?;
+        self.prompt_engine.write().await.update_template_with_feedback(format, &feedback, batch_quality_score)?;
 
         tracing::info!(
             "Updated generator with feedback: {} suggestions, {} avoid patterns",
@@ -196,9 +255,34 @@ impl ConcurrentDatasetGenerator {
         tasks: Vec<GenerationTask>,
         cancellation_token: CancellationToken,
         progress_tx: mpsc::UnboundedSender<ProgressUpdate>,
-    ) -> Result<Vec<DatasetEntry>> {
-        let total_tasks = tasks.len();
-        let batch_semaphore = Arc::new(Semaphore::new(self.config.max_concurrent_batches));
+    ) -> Result<GenerationOutcome> {
+        let total_batches = Arc::new(RwLock::new(tasks.len()));
+        let target_entries: usize = tasks.iter().map(|task| task.entries_to_generate).sum();
+        let templates = tasks.clone();
+        *self.usage.write().await = UsageSummary::default();
+        self.responses.store(0, Ordering::Relaxed);
+        self.quarantined_responses.store(0, Ordering::Relaxed);
+
+        // A fatal error cancels the requests in flight and fails the run
+        let run_token = cancellation_token.child_token();
+        let fatal_error = Arc::new(RwLock::new(None));
+        // Reaching a budget limit stops scheduling batches; those in flight finish and are kept
+        let stop_token = run_token.child_token();
+        let budget_exhausted = Arc::new(RwLock::new(None));
+        let deadline_watch = self.config.budget.max_duration().map(|max_duration| {
+            let stop_token = stop_token.clone();
+            let budget_exhausted = budget_exhausted.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = tokio::time::sleep(max_duration) => {
+                        tracing::warn!("Generation time budget of {:?} reached, not starting further batches", max_duration);
+                        budget_exhausted.write().await.get_or_insert(BudgetLimit::Time);
+                        stop_token.cancel();
+                    }
+                    _ = stop_token.cancelled() => {}
+                }
+            })
+        });
         
         // Statistics tracking
         let start_time = Instant::now();
@@ -210,38 +294,39 @@ impl ConcurrentDatasetGenerator {
         // Results collection
         let results = Arc::new(RwLock::new(HashMap::new()));
 
-        // Create futures for concurrent execution
-        let mut futures = FuturesUnordered::new();
-        
-        for task in tasks {
-            let semaphore = batch_semaphore.clone();
-            let cancellation_token = cancellation_token.clone();
+        // Each task runs as its own future; top-up batches are spawned the same way
+        let spawn_task = |task: GenerationTask| {
+            let cancellation_token = run_token.clone();
+            let stop_token = stop_token.clone();
             let progress_tx = progress_tx.clone();
             let generator = self.clone();
             let completed_batches = completed_batches.clone();
             let total_entries_generated = total_entries_generated.clone();
             let total_errors = total_errors.clone();
             let total_retries = total_retries.clone();
+            let budget_exhausted = budget_exhausted.clone();
+            let fatal_error = fatal_error.clone();
+            let total_batches = total_batches.clone();
             let results = results.clone();
 
-            futures.push(tokio::spawn(async move {
-                // Acquire semaphore permit for batch concurrency control
-                let _permit = semaphore.acquire().await.unwrap();
-                
-                // Check for cancellation
-                if cancellation_token.is_cancelled() {
+            tokio::spawn(async move {
+                // Wait for a slot under the adaptive concurrency limit, unless scheduling stopped meanwhile
+                let _permit = tokio::select! {
+                    permit = generator.concurrency.acquire() => permit,
+                    _ = stop_token.cancelled() => return Ok(()),
+                };
+                if stop_token.is_cancelled() {
                     return Ok(());
                 }
 
                 // Execute the generation task
-                match generator.execute_task_with_retries(task.clone(), cancellation_token.clone()).await {
+                match generator.execute_task_with_fallbacks(task.clone(), cancellation_token.clone()).await {
                     Ok(batch_result) => {
                         tracing::info!("Batch {} completed with {} entries", batch_result.batch_id, batch_result.entries.len());
                         
                         // Update statistics
                         let mut completed = completed_batches.write().await;
                         *completed += 1;
-                        let completed_count = *completed;
                         
                         let mut total_entries = total_entries_generated.write().await;
                         *total_entries += batch_result.entries.len();
@@ -251,6 +336,18 @@ impl ConcurrentDatasetGenerator {
                         *retries += batch_result.retry_count;
                         let retries_count = *retries;
 
+                        let errors_count = *total_errors.read().await;
+                        let parse_success_rate = generator.parse_success_rate();
+                        tracing::info!("Batch {} parse success rate: {:.0}%", 
+                                     batch_result.batch_id, batch_result.parse_success_rate() * 100.0);
+
+                        let usage_snapshot = generator.usage.read().await.clone();
+                        if let Some(limit) = generator.config.budget.exceeded(&usage_snapshot, start_time.elapsed()) {
+                            tracing::warn!("Generation budget reached ({:?}) after batch {}, not starting further batches", limit, batch_result.batch_id);
+                            budget_exhausted.write().await.get_or_insert(limit);
+                            stop_token.cancel();
+                        }
+
                         // Store results
                         let mut results_guard = results.write().await;
                         results_guard.insert(batch_result.batch_id, batch_result.entries.clone());
@@ -260,22 +357,46 @@ impl ConcurrentDatasetGenerator {
                         // Calculate performance metrics
                         let elapsed = start_time.elapsed().as_secs_f64();
                         let entries_per_second = if elapsed > 0.0 { entries_count as f64 / elapsed } else { 0.0 };
-                        let concurrent_batches = total_tasks - completed_count;
+                        let concurrent_batches = generator.concurrency.limit();
 
                         // Send progress update
                         let _ = progress_tx.send(ProgressUpdate {
                             batch_completed: Some(batch_result.batch_id),
                             entries_generated: entries_count,
-                            errors_count: *total_errors.read().await,
+                            errors_count,
                             retries_count,
                             concurrent_batches,
+                            total_batches: *total_batches.read().await,
                             entries_per_second,
+                            parse_success_rate,
+                            batch_parse_success_rate: Some(batch_result.parse_success_rate()),
+                            usage: usage_snapshot,
+                            batch_substitution: batch_result.substitution.clone(),
+                            batch_system_fingerprints: batch_result.system_fingerprints.clone(),
+                            quarantined_responses: generator.quarantined_responses.load(Ordering::Relaxed),
+                            error_class: None,
                         });
                     }
+                    Err(e) if cancellation_token.is_cancelled() => {
+                        tracing::info!("Batch {} stopped: {}", task.batch_id, e);
+                    }
                     Err(e) => {
                         let mut errors = total_errors.write().await;
                         *errors += 1;
-                        tracing::error!("Batch {} failed: {}", task.batch_id, e);
+                        let error_class = classify(&e);
+                        tracing::error!("Batch {} failed ({} error): {}", task.batch_id, error_class, e);
+                        if error_class.is_fatal() {
+                            fatal_error.write().await.get_or_insert_with(|| {
+                                format!("Aborted after a {} error in batch {}: {}", error_class, task.batch_id, e)
+                            });
+                            cancellation_token.cancel();
+                        }
+                        // Failed attempts spend tokens too
+                        if let Some(limit) = generator.config.budget.exceeded(&*generator.usage.read().await, start_time.elapsed()) {
+                            tracing::warn!("Generation budget reached ({:?}) after batch {} failed, not starting further batches", limit, task.batch_id);
+                            budget_exhausted.write().await.get_or_insert(limit);
+                            stop_token.cancel();
+                        }
                         
                         // Send error update
                         let _ = progress_tx.send(ProgressUpdate {
@@ -283,44 +404,129 @@ impl ConcurrentDatasetGenerator {
                             entries_generated: *total_entries_generated.read().await,
                             errors_count: *errors,
                             retries_count: *total_retries.read().await,
-                            concurrent_batches: total_tasks - *completed_batches.read().await,
+                            concurrent_batches: generator.concurrency.limit(),
+                            total_batches: *total_batches.read().await,
                             entries_per_second: 0.0,
+                            parse_success_rate: generator.parse_success_rate(),
+                            batch_parse_success_rate: None,
+                            usage: generator.usage.read().await.clone(),
+                            batch_substitution: None,
+                            batch_system_fingerprints: BTreeSet::new(),
+                            quarantined_responses: generator.quarantined_responses.load(Ordering::Relaxed),
+                            error_class: Some(error_class),
                         });
                     }
                 }
 
                 Ok::<(), anyhow::Error>(())
-            }));
+            })
+        };
+        let mut futures: FuturesUnordered<_> = tasks.into_iter().map(spawn_task).collect();
+
+        let mut top_up_round = 0;
+        let mut all_entries = loop {
+            // Wait for all futures to complete or cancellation
+            while let Some(result) = futures.next().await {
+                if cancellation_token.is_cancelled() {
+                    break;
+                }
+                
+                if let Err(e) = result {
+                    tracing::error!("Task execution error: {}", e);
+                }
+            }
+
+            // Collect all results in batch order, counting only distinct entries of the right shape
+            let results_guard = results.read().await;
+            let mut batch_ids: Vec<usize> = results_guard.keys().copied().collect();
+            batch_ids.sort_unstable();
+            let collected = batch_ids.iter().flat_map(|batch_id| results_guard[batch_id].iter().cloned());
+            let entries = usable_entries(collected, &self.config.dataset_format);
+            drop(results_guard);
+
+            let shortfall = target_entries.saturating_sub(entries.len());
+            if shortfall == 0 || stop_token.is_cancelled() {
+                break entries;
+            }
+            if top_up_round == self.config.max_top_up_rounds {
+                tracing::warn!("Still {} entries short of {} after {} top-up rounds", shortfall, target_entries, top_up_round);
+                break entries;
+            }
+
+            top_up_round += 1;
+            let mut total_batches_guard = total_batches.write().await;
+            let top_up_tasks = top_up_tasks(&templates, shortfall, *total_batches_guard, top_up_round);
+            tracing::info!("{} entries short of {}, scheduling {} top-up batches (round {})",
+                         shortfall, target_entries, top_up_tasks.len(), top_up_round);
+            *total_batches_guard += top_up_tasks.len();
+            drop(total_batches_guard);
+            futures.extend(top_up_tasks.into_iter().map(spawn_task));
+        };
+        all_entries.truncate(target_entries);
+
+        tracing::info!("Final collection: {} entries from {} batches", all_entries.len(), *total_batches.read().await);
+
+        if let Some(deadline_watch) = deadline_watch {
+            deadline_watch.abort();
         }
+        if cancellation_token.is_cancelled() {
+            return Err(anyhow::anyhow!("Generation cancelled"));
+        }
+        if let Some(error) = fatal_error.read().await.clone() {
+            return Err(anyhow::anyhow!(error));
+        }
+        let budget_exhausted = *budget_exhausted.read().await;
+        Ok(GenerationOutcome {
+            entries: all_entries,
+            budget_exhausted,
+        })
+    }
 
-        // Wait for all futures to complete or cancellation
-        while let Some(result) = futures.next().await {
+    /// Run a task on its own model, then on each fallback model in turn once retries are exhausted
+    async fn execute_task_with_fallbacks(
+        &self,
+        task: GenerationTask,
+        cancellation_token: CancellationToken,
+    ) -> Result<BatchResult> {
+        let mut error = match self.execute_task_with_retries(task.clone(), cancellation_token.clone()).await {
+            Ok(batch_result) => return Ok(batch_result),
+            Err(e) => e,
+        };
+
+        // A shared provider only serves the run's own model, so fallbacks create their own
+        let mut fallback_generator = self.clone();
+        fallback_generator.llm = None;
+
+        let fallbacks = self.config.fallback_models.iter().filter(|model| {
+            model.api_model_id() != task.model_id || model.provider != task.provider
+        });
+        for fallback in fallbacks {
             if cancellation_token.is_cancelled() {
                 break;
             }
-            
-            if let Err(e) = result {
-                tracing::error!("Task execution error: {}", e);
-            }
-        }
+            tracing::warn!("Batch {} failed on {}, falling back to {}: {}", task.batch_id, task.model_id, fallback.id, error);
 
-        // Collect all results in order
-        let results_guard = results.read().await;
-        let mut all_entries = Vec::new();
-        
-        tracing::info!("Collecting results from {} tasks, results map has {} entries", total_tasks, results_guard.len());
-        
-        for i in 0..total_tasks {
-            if let Some(entries) = results_guard.get(&i) {
-                tracing::info!("Adding {} entries from batch {}", entries.len(), i);
-                all_entries.extend(entries.clone());
-            } else {
-                tracing::warn!("No results found for batch {}", i);
+            let fallback_task = GenerationTask {
+                model_id: fallback.api_model_id().to_string(),
+                provider: fallback.provider.clone(),
+                endpoint: fallback.endpoint.clone(),
+                ..task.clone()
+            };
+            match fallback_generator.execute_task_with_retries(fallback_task, cancellation_token.clone()).await {
+                Ok(mut batch_result) => {
+                    batch_result.substitution = Some(ModelSubstitution {
+                        original_model: task.model_id.clone(),
+                        model: fallback.api_model_id().to_string(),
+                        provider: fallback.provider.clone(),
+                        reason: error.to_string(),
+                    });
+                    return Ok(batch_result);
+                }
+                Err(e) => error = e,
             }
         }
 
-        tracing::info!("Final collection: {} total entries from {} batches", all_entries.len(), total_tasks);
-        Ok(all_entries)
+        Err(error)
     }
 
     /// Execute a single task with automatic retries and error handling
@@ -330,29 +536,57 @@ impl ConcurrentDatasetGenerator {
         cancellation_token: CancellationToken,
     ) -> Result<BatchResult> {
         let mut last_error = None;
+        // Attempts whose response was quarantined, counted towards the batch's parse success rate
+        let mut unparseable_attempts = 0;
         let start_time = Instant::now();
+        let retry_policy = RetryPolicy::new(self.config.retry_delay, self.config.max_retry_delay);
 
         for retry_count in 0..=self.config.max_retries {
             if cancellation_token.is_cancelled() {
                 return Err(anyhow::anyhow!("Generation cancelled"));
             }
 
-            match self.execute_single_batch(&task, cancellation_token.clone()).await {
-                Ok(entries) => {
+            let attempt_id = uuid::Uuid::new_v4().to_string();
+            let result = self.execute_single_batch(&task, &attempt_id, cancellation_token.clone()).await;
+            if let (Err(_), Some(entry_tx)) = (&result, &self.entry_tx) {
+                let _ = entry_tx.send(StreamEvent::Discarded { batch_id: task.batch_id, attempt_id });
+            }
+            match result {
+                Ok(parsed_batches) => {
+                    let parsed_responses = parsed_batches.len();
+                    let system_fingerprints = parsed_batches
+                        .iter()
+                        .filter_map(|batch| batch.system_fingerprint.clone())
+                        .collect();
                     return Ok(BatchResult {
                         batch_id: task.batch_id,
-                        entries,
+                        entries: parsed_batches.into_iter().flat_map(|batch| batch.entries).collect(),
                         generation_time: start_time.elapsed(),
                         retry_count,
+                        responses: parsed_responses + unparseable_attempts,
+                        parsed_responses,
+                        substitution: None,
+                        system_fingerprints,
                     });
                 }
                 Err(e) => {
-                    last_error = Some(e);
+                    if e.is::<UnparseableResponse>() {
+                        unparseable_attempts += 1;
+                    }
+                    let error_class = classify(&e);
+                    if error_class.is_fatal() || cancellation_token.is_cancelled() {
+                        return Err(e);
+                    }
                     if retry_count < self.config.max_retries {
-                        tracing::warn!("Batch {} failed, retrying in {:?} (attempt {}/{})", 
-                                     task.batch_id, self.config.retry_delay, retry_count + 1, self.config.max_retries);
-                        tokio::time::sleep(self.config.retry_delay).await;
+                        let delay = retry_policy.delay(retry_count);
+                        tracing::warn!("Batch {} failed with a {} error, retrying in {:?} (attempt {}/{}): {}",
+                                     task.batch_id, error_class, delay, retry_count + 1, self.config.max_retries, e);
+                        tokio::select! {
+                            _ = tokio::time::sleep(delay) => {}
+                            _ = cancellation_token.cancelled() => return Err(e),
+                        }
                     }
+                    last_error = Some(e);
                 }
             }
         }
@@ -364,8 +598,9 @@ impl ConcurrentDatasetGenerator {
     async fn execute_single_batch(
         &self,
         task: &GenerationTask,
+        attempt_id: &str,
         cancellation_token: CancellationToken,
-    ) -> Result<Vec<DatasetEntry>> {
+    ) -> Result<Vec<ParsedBatch>> {
         // For large batches, split into parallel sub-requests
         let sub_batch_size = if task.entries_to_generate > self.config.max_concurrent_requests_per_batch {
             task.entries_to_generate / self.config.max_concurrent_requests_per_batch
@@ -387,234 +622,254 @@ impl ConcurrentDatasetGenerator {
         // Execute sub-requests concurrently
         let mut futures = FuturesUnordered::new();
         
-        for (_sub_id, size) in sub_tasks {
-            let task_clone = task.clone();
+        for (sub_id, size) in sub_tasks {
+            let mut task_clone = task.clone();
+            task_clone.seed = task.seed.map(|seed| derive_seed(seed, sub_id as u64));
             let cancellation_token = cancellation_token.clone();
             let generator = self.clone();
+            let attempt_id = attempt_id.to_string();
 
             futures.push(tokio::spawn(async move {
-                generator.execute_api_request(
-                    &task_clone.model_id,
-                    &task_clone.provider,
-                    &task_clone.goal,
-                    size,
-                    &task_clone.context,
-                    cancellation_token,
-                ).await
+                generator.generate_provider_batch(&task_clone, size, &attempt_id, cancellation_token).await
             }));
         }
 
         // Collect results from all sub-requests
-        let mut all_entries = Vec::new();
+        let mut parsed_batches = Vec::new();
         while let Some(result) = futures.next().await {
             if cancellation_token.is_cancelled() {
                 return Err(anyhow::anyhow!("Generation cancelled"));
             }
 
             match result {
-                Ok(Ok(entries)) => all_entries.extend(entries),
+                Ok(Ok(batch)) => parsed_batches.push(batch),
                 Ok(Err(e)) => return Err(e),
                 Err(e) => return Err(anyhow::anyhow!("Sub-task failed: {}", e)),
             }
         }
 
-        Ok(all_entries)
+        Ok(parsed_batches)
+    }
+
+    /// The limiter for a task's provider and endpoint, with local or hosted limits by provider
+    fn rate_limiter(&self, task: &GenerationTask) -> RateLimiter {
+        let key = (task.provider.clone(), task.endpoint.clone());
+        self.rate_limiters
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| match task.provider {
+                ModelProvider::Ollama | ModelProvider::OpenAICompatible => RateLimiter::new(self.config.ollama_rate_limits),
+                ModelProvider::OpenAI | ModelProvider::Anthropic => RateLimiter::new(self.config.openai_rate_limits),
+            })
+            .clone()
+    }
+
+    /// Render a batch prompt through the template engine, with the latest validation feedback
+    async fn create_generation_prompt(&self, task: &GenerationTask, batch_size: usize) -> Result<GenerationPrompt> {
+        let validation_feedback = self.validation_feedback_history.read().await.last().cloned();
+        let context = PromptContext {
+            previous_batches_summary: task.context.clone(),
+            common_errors: validation_feedback.as_ref().map(|feedback| feedback.common_issues.clone()).unwrap_or_default(),
+            validation_feedback,
+            ..Default::default()
+        };
+        self.prompt_engine.read().await.generate_prompt(
+            &self.config.dataset_format,
+            &task.goal,
+            batch_size,
+            &context,
+            &task.domain_context,
+        )
     }
 
-    /// Execute a single API request with rate limiting
-    async fn execute_api_request(
+    /// Generate a batch through the provider abstraction
+    async fn generate_provider_batch(
         &self,
-        model_id: &str,
-        provider: &ModelProvider,
-        goal: &str,
+        task: &GenerationTask,
         batch_size: usize,
-        context: &str,
+        attempt_id: &str,
         cancellation_token: CancellationToken,
-    ) -> Result<Vec<DatasetEntry>> {
-        // Apply rate limiting based on provider
-        let rate_limiter = match provider {
-            ModelProvider::Ollama => &self.ollama_rate_limiter,
-            ModelProvider::OpenAI => &self.openai_rate_limiter,
+    ) -> Result<ParsedBatch> {
+        let provider = &task.provider;
+        let llm = match &self.llm {
+            Some(llm) => llm.clone(),
+            None => create_provider(
+                provider,
+                task.endpoint.as_deref(),
+                &self.config.provider_settings,
+                self.client.clone(),
+            )?,
+        };
+        // The archive sits inside the cache so that only calls that reach the provider are recorded
+        let llm = match &self.archive {
+            Some(archive) => ArchivingProvider::wrap(llm, archive.clone(), &task.id, task.batch_id),
+            None => llm,
         };
+        // Responses that would be quarantined are not cached, so a rerun asks the model again
+        let llm = if self.config.use_response_cache {
+            Arc::new(
+                CachedProvider::new(llm, self.response_cache.clone())
+                    .caching_only(|response| parse_generated_entries(&response.text).is_ok()),
+            )
+        } else {
+            llm
+        };
+        let prompt = self.create_generation_prompt(task, batch_size).await?;
 
-        rate_limiter.wait_for_permit().await;
+        let request = CompletionRequest::new(task.model_id.clone(), prompt.user_message())
+            .with_system(prompt.system_prompt)
+            .with_response_schema(ResponseSchema::for_batch(&self.config.dataset_format));
+        let request = task.sampling.apply(request);
+        let request = match task.seed {
+            Some(seed) => request.with_seed(seed),
+            None => request,
+        };
 
-        if cancellation_token.is_cancelled() {
-            return Err(anyhow::anyhow!("Generation cancelled"));
+        let rate_limiter = self.rate_limiter(task);
+        let estimated_tokens = estimate_tokens(&request);
+        tokio::select! {
+            _ = rate_limiter.acquire(estimated_tokens) => {}
+            _ = cancellation_token.cancelled() => {
+                return Err(anyhow::anyhow!("Generation cancelled"));
+            }
         }
 
-        match provider {
-            ModelProvider::Ollama => {
-                self.generate_ollama_batch(model_id, goal, batch_size, context, cancellation_token).await
+        // Dropping the request future on cancellation closes the connection, even mid-stream
+        let request_started = Instant::now();
+        let result = tokio::select! {
+            result = self.complete(llm.as_ref(), &request, task.batch_id, attempt_id) => result,
+            _ = cancellation_token.cancelled() => {
+                return Err(anyhow::anyhow!("Request cancelled"));
             }
-            ModelProvider::OpenAI => {
-                self.generate_openai_batch(model_id, goal, batch_size, context, cancellation_token).await
+        };
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.concurrency.record_error(classify(&e));
+                if let Some(rate_limited) = e.downcast_ref::<RateLimitedError>() {
+                    let delay = rate_limiter.back_off(rate_limited.retry_after);
+                    tracing::warn!("{:?} rate limited batch {}, pausing requests for {:?}", provider, task.batch_id, delay);
+                }
+                return Err(e);
             }
-        }
-    }
-
-    /// Optimized Ollama batch generation
-    async fn generate_ollama_batch(
-        &self,
-        model_id: &str,
-        goal: &str,
-        batch_size: usize,
-        context: &str,
-        cancellation_token: CancellationToken,
-    ) -> Result<Vec<DatasetEntry>> {
-        let prompt = self.create_optimized_prompt(goal, batch_size, context);
-        
-        let request_body = serde_json::json!({
-            "model": model_id,
-            "prompt": prompt,
-            "stream": false,
-            "options": {
-                "temperature": 0.7,
-                "top_p": 0.9,
-                "top_k": 40
+        };
+        // A cached response spent no tokens and says nothing about the provider's limits or latency
+        if !response.cached {
+            rate_limiter.record_response(estimated_tokens, response.usage.as_ref(), response.rate_limit.as_ref());
+            self.concurrency.record_success(request_started.elapsed());
+            self.responses.fetch_add(1, Ordering::Relaxed);
+            // Recorded before parsing, so tokens spent on responses that fail or whose attempt is retried still count
+            if let Some(usage) = &response.usage {
+                self.usage.write().await.record(task.batch_id, provider, &task.model_id, usage, &self.prices);
             }
-        });
+        }
 
-        let request = self.client
-            .post("http://localhost:11434/api/generate")
-            .json(&request_body);
+        tracing::info!("{:?} response received, length: {} chars", provider, response.text.len());
+        tracing::debug!("{:?} response content: {}", provider, response.text);
 
-        let response = tokio::select! {
-            result = request.send() => result?,
-            _ = cancellation_token.cancelled() => {
-                return Err(anyhow::anyhow!("Request cancelled"));
+        // An unparseable response is kept for review and fails the attempt, so the batch is retried
+        let mut entries = match parse_generated_entries(&response.text) {
+            Ok(entries) => entries,
+            Err(unparseable) => {
+                tracing::warn!("Quarantining {:?} response for batch {}: {}", provider, task.batch_id, unparseable.error);
+                self.quarantine.add(QuarantinedResponse::new(task, &self.generation_id, batch_size, request, unparseable.clone()));
+                self.quarantined_responses.fetch_add(1, Ordering::Relaxed);
+                return Err(unparseable.into());
             }
         };
-
-        if response.status().is_success() {
-            let result: serde_json::Value = response.json().await?;
-            let generated_text = result["response"].as_str().unwrap_or("[]");
-            
-            tracing::info!("Ollama response received, length: {} chars", generated_text.len());
-            tracing::debug!("Ollama response content: {}", generated_text);
-            
-            let entries = self.parse_generated_entries(generated_text, batch_size)?;
-            tracing::info!("Parsed {} entries from Ollama response", entries.len());
-            Ok(entries)
-        } else {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            tracing::error!("Ollama API error: {} - {}", status, error_text);
-            Err(anyhow::anyhow!("Ollama API error: {} - {}", status, error_text))
+        for entry in &mut entries {
+            entry.source_model = Some(task.model_id.clone());
         }
+        tracing::info!("Parsed {} entries from {:?} response", entries.len(), provider);
+        Ok(ParsedBatch {
+            entries,
+            system_fingerprint: response.system_fingerprint,
+        })
     }
 
-    /// Optimized OpenAI batch generation
-    async fn generate_openai_batch(
-        &self,
-        model_id: &str,
-        goal: &str,
-        batch_size: usize,
-        context: &str,
-        cancellation_token: CancellationToken,
-    ) -> Result<Vec<DatasetEntry>> {
-        let api_key = std::env::var("OPENAI_API_KEY")
-            .map_err(|_| anyhow::anyhow!(
-                "OPENAI_API_KEY not found in environment. Please set it in your .env file or system environment"
-            ))?;
-
-        let prompt = self.create_optimized_prompt(goal, batch_size, context);
-        
-        let request_body = serde_json::json!({
-            "model": model_id,
-            "messages": [
-                {
-                    "role": "system",
-                    "content": "You are an expert at creating high-quality training datasets. Always respond with valid JSON arrays containing the requested training examples."
-                },
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ],
-            "temperature": 0.7,
-            "max_tokens": 4000,
-            "top_p": 0.9
-        });
-
-        let request = self.client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body);
+    /// Share of the run's responses that parsed
+    fn parse_success_rate(&self) -> f32 {
+        // Loaded separately, so another task may count a response in between
+        let responses = self.responses.load(Ordering::Relaxed);
+        let quarantined = self.quarantined_responses.load(Ordering::Relaxed);
+        success_rate(responses.saturating_sub(quarantined), responses)
+    }
 
-        let response = tokio::select! {
-            result = request.send() => result?,
-            _ = cancellation_token.cancelled() => {
-                return Err(anyhow::anyhow!("Request cancelled"));
+    /// Run the request, streaming entries out as they complete when an entry stream is attached
+    async fn complete(
+        &self,
+        llm: &dyn LlmProvider,
+        request: &CompletionRequest,
+        batch_id: usize,
+        attempt_id: &str,
+    ) -> Result<CompletionResponse> {
+        match &self.entry_tx {
+            Some(entry_tx) => {
+                let mut parser = IncrementalEntryParser::new();
+                llm.complete_stream(request, &mut |delta| {
+                    for data in parser.push(delta) {
+                        let entry = DatasetEntry { data, source_model: Some(request.model.clone()) };
+                        let _ = entry_tx.send(StreamEvent::Entry { batch_id, attempt_id: attempt_id.to_string(), entry });
+                    }
+                })
+                .await
             }
-        };
-
-        if response.status().is_success() {
-            let result: serde_json::Value = response.json().await?;
-            let generated_text = result["choices"][0]["message"]["content"]
-                .as_str()
-                .unwrap_or("[]");
-            
-            self.parse_generated_entries(generated_text, batch_size)
-        } else {
-            let error_text = response.text().await.unwrap_or_default();
-            Err(anyhow::anyhow!("OpenAI API error: {}", error_text))
+            None => llm.complete(request).await,
         }
     }
+}
 
-    /// Create an optimized prompt for better generation quality
-    fn create_optimized_prompt(&self, goal: &str, batch_size: usize, context: &str) -> String {
-        let format_schema = match self.config.dataset_format {
-            crate::types::DatasetFormat::RetrievalEmbedding => "{\"query\": \"...\", \"positive_passage\": \"...\", \"negative_passages\": [\"...\", \"...\"]}",
-            crate::types::DatasetFormat::Alpaca => "{\"instruction\": \"...\", \"input\": \"...\", \"output\": \"...\"}",
-            // ...add other formats as needed...
-            _ => "{...}"
-        };
-        format!(
-            "Generate {} training examples for fine-tuning goal: {}. Context: {}.\n\nReturn only a JSON array of objects matching this exact schema: {}.\nDo not use any other format.\nGoal: {}",
-            batch_size, goal, context, format_schema, goal
-        )
+/// Parse the entries out of a model response, recovering what it can from malformed JSON
+pub fn parse_generated_entries(text: &str) -> std::result::Result<Vec<DatasetEntry>, UnparseableResponse> {
+    match recover_entries(text) {
+        Ok(values) => {
+            tracing::info!("Successfully parsed {} entries from JSON", values.len());
+            Ok(values.into_iter().map(|data| DatasetEntry { data, source_model: None }).collect())
+        }
+        Err(error) => Err(UnparseableResponse { raw_output: text.to_string(), error }),
     }
+}
 
-    /// Generate fallback entries when parsing fails
-    fn generate_fallback_entries(&self, count: usize) -> Vec<DatasetEntry> {
-        let format = &self.config.dataset_format;
-        (0..count)
-            .map(|i| {
-                let data = match format {
-                    crate::types::DatasetFormat::Alpaca => serde_json::json!({
-                        "instruction": format!("Sample instruction {}", i + 1),
-                        "input": format!("Sample input context {}", i + 1),
-                        "output": format!("Sample response output {}", i + 1)
-                    }),
-                    crate::types::DatasetFormat::RetrievalEmbedding => serde_json::json!({
-                        "query": format!("Sample query {}", i + 1),
-                        "positive_passage": format!("Relevant passage {}", i + 1),
-                        "negative_passages": [format!("Irrelevant passage {}", i + 1), format!("Another irrelevant passage {}", i + 1)]
-                    }),
-                    // ...add other formats as needed...
-                    _ => serde_json::json!({
-                        "instruction": format!("Sample instruction {}", i + 1),
-                        "input": format!("Sample input context {}", i + 1),
-                        "output": format!("Sample response output {}", i + 1)
-                    })
-                };
-                DatasetEntry { data }
-            })
-            .collect()
+fn success_rate(parsed: usize, total: usize) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        parsed as f32 / total as f32
     }
+}
 
+/// Extra batches covering `shortfall` entries, numbered from `first_batch_id`
+///
+/// Each batch copies the model, prompt and sampling of an original task in turn.
+fn top_up_tasks(templates: &[GenerationTask], shortfall: usize, first_batch_id: usize, round: usize) -> Vec<GenerationTask> {
+    let batch_size = templates.iter().map(|task| task.entries_to_generate).max().unwrap_or(0).max(1);
+    let batches = shortfall.div_ceil(batch_size);
+    templates
+        .iter()
+        .cycle()
+        .take(batches)
+        .enumerate()
+        .map(|(index, template)| {
+            let batch_id = first_batch_id + index;
+            GenerationTask {
+                id: uuid::Uuid::new_v4().to_string(),
+                batch_id,
+                entries_to_generate: (shortfall - index * batch_size).min(batch_size),
+                context: format!("Top-up batch (round {}) replacing {} entries that earlier batches did not deliver.", round, shortfall),
+                seed: template.seed.map(|seed| derive_seed(seed, batch_id as u64)),
+                ..template.clone()
+            }
+        })
+        .collect()
 }
 
-// Implement Clone for SimpleRateLimiter
-impl Clone for SimpleRateLimiter {
-    fn clone(&self) -> Self {
-        Self {
-            last_request: self.last_request.clone(),
-            min_interval: self.min_interval,
-        }
-    }
+/// Derive an independent seed for a batch or sub-request from the run seed (splitmix64)
+pub fn derive_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
 
 // Implement Clone for the generator (needed for moving into async tasks)
@@ -622,11 +877,166 @@ impl Clone for ConcurrentDatasetGenerator {
     fn clone(&self) -> Self {
         Self {
             config: self.config.clone(),
-            ollama_rate_limiter: self.ollama_rate_limiter.clone(),
-            openai_rate_limiter: self.openai_rate_limiter.clone(),
+            rate_limiters: self.rate_limiters.clone(),
+            concurrency: self.concurrency.clone(),
             client: self.client.clone(),
-            prompt_engine: PromptTemplateEngine::new(), // Create new instance for clone
+            prompt_engine: self.prompt_engine.clone(),
             validation_feedback_history: self.validation_feedback_history.clone(),
+            llm: self.llm.clone(),
+            entry_tx: self.entry_tx.clone(),
+            quarantine: self.quarantine.clone(),
+            archive: self.archive.clone(),
+            response_cache: self.response_cache.clone(),
+            generation_id: self.generation_id.clone(),
+            usage: self.usage.clone(),
+            prices: self.prices.clone(),
+            responses: self.responses.clone(),
+            quarantined_responses: self.quarantined_responses.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_provider::{EmbeddingRequest, EmbeddingResponse, TokenUsage};
+    use crate::usage::GenerationBudget;
+
+    /// Answers with prose first and with a valid batch afterwards
+    struct UnparseableOnceProvider {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for UnparseableOnceProvider {
+        fn kind(&self) -> ModelProvider {
+            ModelProvider::Ollama
         }
+
+        async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+            let text = match self.calls.fetch_add(1, Ordering::SeqCst) {
+                0 => "Sure! Here are your examples:",
+                _ => r#"{"entries": [{"instruction": "a", "output": "b"}, {"instruction": "c", "output": "d"}]}"#,
+            };
+            Ok(CompletionResponse {
+                text: text.to_string(),
+                model: request.model.clone(),
+                finish_reason: None,
+                usage: None,
+                system_fingerprint: None,
+                rate_limit: None,
+                cached: false,
+            })
+        }
+
+        async fn embed(&self, _request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+            Err(anyhow::anyhow!("not supported"))
+        }
+    }
+
+    /// Answers every request with a valid batch that cost a million prompt and completion tokens
+    struct PricedProvider {
+        calls: AtomicUsize,
     }
-}
\ No newline at end of file
+
+    #[async_trait::async_trait]
+    impl LlmProvider for PricedProvider {
+        fn kind(&self) -> ModelProvider {
+            ModelProvider::OpenAI
+        }
+
+        async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(CompletionResponse {
+                text: r#"{"entries": [{"instruction": "a", "output": "b"}, {"instruction": "c", "output": "d"}]}"#.to_string(),
+                model: request.model.clone(),
+                finish_reason: None,
+                usage: Some(TokenUsage { prompt_tokens: 1_000_000, completion_tokens: 1_000_000 }),
+                system_fingerprint: None,
+                rate_limit: None,
+                cached: false,
+            })
+        }
+
+        async fn embed(&self, _request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+            Err(anyhow::anyhow!("not supported"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quarantined_response_is_retried() {
+        let config = ConcurrentGenerationConfig {
+            retry_delay: Duration::from_millis(1),
+            provider_settings: ProviderSettings::default(),
+            ..Default::default()
+        };
+        let quarantine = QuarantineStore::new();
+        let provider = Arc::new(UnparseableOnceProvider { calls: AtomicUsize::new(0) });
+        let generator = ConcurrentDatasetGenerator::new(config)
+            .with_provider(provider.clone())
+            .with_quarantine(quarantine.clone());
+        let task = GenerationTask { entries_to_generate: 2, ..GenerationTask::for_test(0, "llama3") };
+
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        let outcome = generator.generate_concurrent(vec![task], CancellationToken::new(), progress_tx).await.unwrap();
+        assert_eq!(outcome.entries.len(), 2);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(quarantine.len(), 1);
+
+        let update = progress_rx.recv().await.unwrap();
+        assert_eq!((update.retries_count, update.quarantined_responses), (1, 1));
+        assert_eq!(update.batch_parse_success_rate, Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn test_cached_responses_do_not_count_towards_the_budget() {
+        let dir = std::env::temp_dir().join(format!("response_cache_test_{}", uuid::Uuid::new_v4()));
+        let cache = Arc::new(ResponseCache::new(&dir));
+        let provider = Arc::new(PricedProvider { calls: AtomicUsize::new(0) });
+        let task = GenerationTask {
+            entries_to_generate: 2,
+            provider: ModelProvider::OpenAI,
+            ..GenerationTask::for_test(0, "gpt-4o")
+        };
+        let run = |budget: GenerationBudget| {
+            let config = ConcurrentGenerationConfig { use_response_cache: true, budget, ..Default::default() };
+            ConcurrentDatasetGenerator::new(config)
+                .with_provider(provider.clone())
+                .with_response_cache(cache.clone())
+        };
+
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        run(GenerationBudget::default()).generate_concurrent(vec![task.clone()], CancellationToken::new(), progress_tx).await.unwrap();
+        assert!(progress_rx.recv().await.unwrap().usage.estimated_cost_usd.unwrap() > 1.0);
+
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        let budget = GenerationBudget { max_cost_usd: Some(1.0), ..Default::default() };
+        let outcome = run(budget).generate_concurrent(vec![task], CancellationToken::new(), progress_tx).await.unwrap();
+        assert_eq!((outcome.entries.len(), outcome.budget_exhausted), (2, None));
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+
+        let update = progress_rx.recv().await.unwrap();
+        assert_eq!((update.usage.total_tokens(), update.usage.estimated_cost_usd), (0, None));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_top_up_tasks_cover_the_shortfall() {
+        let template = |batch_id: usize, model_id: &str| GenerationTask { seed: Some(7), ..GenerationTask::for_test(batch_id, model_id) };
+        let templates = [template(0, "a"), template(1, "b")];
+
+        let tasks = top_up_tasks(&templates, 25, 2, 1);
+        assert_eq!(tasks.iter().map(|task| task.batch_id).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(tasks.iter().map(|task| task.entries_to_generate).collect::<Vec<_>>(), [10, 10, 5]);
+        assert_eq!(tasks.iter().map(|task| task.model_id.as_str()).collect::<Vec<_>>(), ["a", "b", "a"]);
+        assert_eq!(tasks[0].seed, Some(derive_seed(7, 2)));
+
+        let entry = |value: serde_json::Value| DatasetEntry { data: value, source_model: None };
+        let entries = vec![
+            entry(serde_json::json!({"instruction": "a", "output": "b"})),
+            entry(serde_json::json!({"instruction": "a", "output": "b"})),
+            entry(serde_json::json!({"question": "a"})),
+        ];
+        assert_eq!(usable_entries(entries, &DatasetFormat::Alpaca).len(), 1);
+    }
+}
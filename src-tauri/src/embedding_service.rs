@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
 use crate::quality_validator::ValidatedEntry;
+use crate::llm_provider::{EmbeddingRequest, LlmProvider, OllamaProvider};
+use crate::response_cache::CachedProvider;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingResult {
@@ -12,14 +15,20 @@ pub struct EmbeddingResult {
 }
 
 pub struct EmbeddingService {
-    client: reqwest::Client,
+    provider: Arc<dyn LlmProvider>,
     model_name: String,
 }
 
 impl EmbeddingService {
-    pub fn new(model_name: Option<String>) -> Self {
+    /// Create an embedding service on the Ollama server at `ollama_base_url`
+    pub fn new(model_name: Option<String>, ollama_base_url: &str) -> Self {
+        Self::with_provider(model_name, Arc::new(OllamaProvider::new(reqwest::Client::new(), ollama_base_url)))
+    }
+
+    /// Create an embedding service backed by the given provider
+    pub fn with_provider(model_name: Option<String>, provider: Arc<dyn LlmProvider>) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            provider: CachedProvider::shared(provider),
             model_name: model_name.unwrap_or_else(|| "nomic-embed-text".to_string()),
         }
     }
@@ -202,34 +211,13 @@ impl EmbeddingService {
 
     /// Generate embedding using Ollama's nomic-embed-text model
     async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
-        let request_body = serde_json::json!({
-            "model": self.model_name,
-            "prompt": text
-        });
-
-        let response = self.client
-            .post("http://localhost:11434/api/embeddings")
-            .json(&request_body)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let result: serde_json::Value = response.json().await?;
-            
-            if let Some(embedding_array) = result["embedding"].as_array() {
-                let embedding: Result<Vec<f32>, _> = embedding_array
-                    .iter()
-                    .map(|v| v.as_f64().map(|f| f as f32).ok_or_else(|| anyhow::anyhow!("Invalid embedding value")))
-                    .collect();
-                
-                embedding
-            } else {
-                Err(anyhow::anyhow!("Invalid embedding response format"))
-            }
-        } else {
-            let error_text = response.text().await.unwrap_or_default();
-            Err(anyhow::anyhow!("Ollama embedding API error: {}", error_text))
-        }
+        let request = EmbeddingRequest {
+            model: self.model_name.clone(),
+            input: text.to_string(),
+        };
+
+        let response = self.provider.embed(&request).await?;
+        Ok(response.embedding)
     }
 }
 
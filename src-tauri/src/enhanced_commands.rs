@@ -324,8 +324,10 @@ async fn run_enhanced_generation_process(
                 let validated_entry = crate::quality_validator::ValidatedEntry {
                     entry: result.llm_based_result.tags.first().map(|_| crate::types::DatasetEntry {
                         data: serde_json::json!({}), // Placeholder
+                        source_model: None,
                     }).unwrap_or_else(|| crate::types::DatasetEntry {
                         data: serde_json::json!({}),
+                        source_model: None,
                     }),
                     quality_score: result.final_score,
                     metadata: crate::quality_validator::EntryMetadata {
@@ -443,6 +445,7 @@ async fn generate_enhanced_batch(
                 "input": "Enhanced input context",
                 "output": "Enhanced output response"
             }),
+            source_model: None,
         });
     }
 
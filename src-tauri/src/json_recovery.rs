@@ -0,0 +1,174 @@
+use serde_json::Value;
+
+use crate::structured_output::{extract_entries, IncrementalEntryParser};
+
+/// Keys models commonly wrap their list of examples in, besides the structured `entries`
+const WRAPPER_KEYS: &[&str] = &["examples", "data", "items", "results", "dataset", "samples", "training_examples"];
+
+/// Pull the entry objects out of a model response, repairing common formatting problems
+///
+/// Accepts code fences, JSON Lines, wrapper objects, single objects and trailing commas,
+/// and salvages the complete objects of an array cut off by the token limit.
+pub fn recover_entries(text: &str) -> Result<Vec<Value>, String> {
+    let cleaned = remove_trailing_commas(&strip_code_fences(text));
+    let parse_error = match serde_json::from_str::<Value>(cleaned.trim()) {
+        Ok(value) => return non_empty(entries_from_value(value)),
+        Err(e) => e,
+    };
+
+    let Some(start) = cleaned.find(['[', '{']) else {
+        return Err(format!("No JSON found in response: {}", parse_error));
+    };
+    let body = &cleaned[start..];
+
+    if body.starts_with('{') {
+        // One object, or several in a row as JSON Lines
+        let mut values: Vec<Value> = serde_json::Deserializer::from_str(body)
+            .into_iter()
+            .map_while(Result::ok)
+            .collect();
+        if values.len() == 1 {
+            return non_empty(entries_from_value(values.remove(0)));
+        }
+        if !values.is_empty() {
+            return non_empty(values.into_iter().filter(Value::is_object).collect());
+        }
+    } else if let Some(end) = body.rfind(']') {
+        if let Ok(value) = serde_json::from_str(&body[..=end]) {
+            return non_empty(entries_from_value(value));
+        }
+    }
+
+    // Keep the complete objects of a truncated array
+    let salvaged = IncrementalEntryParser::new().push(body);
+    if salvaged.is_empty() {
+        return Err(format!("Failed to parse response as JSON: {}", parse_error));
+    }
+    tracing::info!("Salvaged {} complete entries from a malformed or truncated response", salvaged.len());
+    Ok(salvaged)
+}
+
+/// The first JSON value in a model response, after removing code fences and trailing commas
+pub fn recover_value(text: &str) -> Result<Value, String> {
+    let cleaned = remove_trailing_commas(&strip_code_fences(text));
+    let start = cleaned.find(['[', '{']).ok_or("No JSON found in response")?;
+    serde_json::Deserializer::from_str(&cleaned[start..])
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No JSON found in response".to_string())?
+        .map_err(|e| format!("Failed to parse response as JSON: {}", e))
+}
+
+fn non_empty(entries: Vec<Value>) -> Result<Vec<Value>, String> {
+    if entries.is_empty() {
+        Err("Response contained no entries".to_string())
+    } else {
+        Ok(entries)
+    }
+}
+
+/// A bare array, a wrapper object around one, or a single entry object
+fn entries_from_value(value: Value) -> Vec<Value> {
+    let value = match extract_entries(value.clone()) {
+        Some(entries) => return entries,
+        None => value,
+    };
+    match value {
+        Value::Object(mut object) => {
+            let wrapped = WRAPPER_KEYS.iter().find_map(|key| match object.get(*key) {
+                Some(Value::Array(_)) => object.remove(*key),
+                _ => None,
+            });
+            match wrapped {
+                Some(Value::Array(entries)) => entries,
+                _ => vec![Value::Object(object)],
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The contents of the markdown code fences in `text`, or `text` itself when it has none
+///
+/// An unclosed fence, as left by a truncated response, runs to the end of the text.
+fn strip_code_fences(text: &str) -> String {
+    if !text.contains("```") {
+        return text.to_string();
+    }
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find("```") {
+        // Skip the language tag on the opening line
+        let after_open = &rest[open + 3..];
+        let content_start = after_open.find('\n').map_or(after_open.len(), |newline| newline + 1);
+        let content = &after_open[content_start..];
+        match content.find("```") {
+            Some(close) => {
+                blocks.push(&content[..close]);
+                rest = &content[close + 3..];
+            }
+            None => {
+                blocks.push(content);
+                break;
+            }
+        }
+    }
+    blocks.join("\n")
+}
+
+/// Drop commas directly before a closing bracket, outside of strings
+fn remove_trailing_commas(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars.clone().find(|c| !c.is_whitespace());
+            if matches!(next, Some(']' | '}')) {
+                continue;
+            }
+        }
+        output.push(c);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_recovers_entries_from_malformed_responses() {
+        let fenced = "Here you go:\n```json\n[{\"instruction\": \"a\", \"output\": \"b\",},]\n```\nEnjoy!";
+        assert_eq!(recover_entries(fenced).unwrap(), vec![json!({"instruction": "a", "output": "b"})]);
+
+        let jsonl = "{\"instruction\": \"a\"}\n{\"instruction\": \"b\"}\n";
+        assert_eq!(recover_entries(jsonl).unwrap().len(), 2);
+
+        let wrapped = "{\"examples\": [{\"instruction\": \"a\"}, {\"instruction\": \"b\"}]}";
+        assert_eq!(recover_entries(wrapped).unwrap().len(), 2);
+
+        let conversation = "{\"messages\": [{\"role\": \"user\", \"content\": \"hi, there\"}]}";
+        assert_eq!(recover_entries(conversation).unwrap().len(), 1);
+
+        let truncated = "[{\"instruction\": \"a\"}, {\"instruction\": \"b\"}, {\"instruction\": \"c";
+        assert_eq!(recover_entries(truncated).unwrap().len(), 2);
+
+        assert!(recover_entries("I cannot help with that.").is_err());
+        assert!(recover_entries("[]").is_err());
+
+        let score = recover_value("Scores:\n```\n{\"overall_score\": 0.8,}\n```").unwrap();
+        assert_eq!(score["overall_score"], json!(0.8));
+    }
+}
@@ -4,6 +4,8 @@ use crate::types::{DatasetEntry, DatasetFormat};
 use crate::quality_validator::{QualityValidator, ValidatedEntry, ValidationConfig, ValidationFeedback};
 use crate::embedding_service::{EmbeddingService, EmbeddingConfig};
 use crate::vector_db::{VectorDbService, CollectionInfo, SearchResult, QueryRequest, VectorDbConfig};
+use crate::llm_provider::create_provider;
+use crate::settings::ProviderSettings;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KnowledgeBaseConfig {
@@ -43,17 +45,23 @@ pub struct KnowledgeBaseManager {
 }
 
 impl KnowledgeBaseManager {
-    pub fn new(config: KnowledgeBaseConfig) -> Self {
-        let validator = QualityValidator::new(Some(config.validation.model_name.clone()));
-        let embedding_service = EmbeddingService::new(Some(config.embedding.model_name.clone()));
-        let vector_db = VectorDbService::new(Some(config.vector_db.base_url.clone()));
-
-        Self {
+    pub fn new(config: KnowledgeBaseConfig, settings: &ProviderSettings) -> Result<Self> {
+        let validation_provider = create_provider(
+            &config.validation.provider,
+            config.validation.endpoint.as_deref(),
+            settings,
+            reqwest::Client::new(),
+        )?;
+        let validator = QualityValidator::with_provider(Some(config.validation.model_name.clone()), validation_provider);
+        let embedding_service = EmbeddingService::new(Some(config.embedding.model_name.clone()), settings.ollama_base_url());
+        let vector_db = VectorDbService::new(Some(config.vector_db.base_url.clone()), settings.ollama_base_url());
+
+        Ok(Self {
             validator,
             embedding_service,
             vector_db,
             config,
-        }
+        })
     }
 
     /// Initialize the knowledge base system
@@ -1,12 +1,26 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
 pub mod commands;
+pub mod concurrency;
 pub mod dataset;
 pub mod dataset_concurrent;
+pub mod json_recovery;
+pub mod llm_provider;
+pub mod settings;
+pub mod structured_output;
 pub mod models;
+pub mod model_mix;
+pub mod ollama_pool;
 pub mod state;
 pub mod types;
+pub mod usage;
 pub mod quality_validator;
+pub mod rate_limiter;
+pub mod quarantine;
+pub mod request_archive;
+pub mod response_cache;
+pub mod retry;
+pub mod sampling;
 pub mod embedding_service;
 pub mod vector_db;
 pub mod knowledge_base;
@@ -18,6 +32,8 @@ pub mod chromadb_server;
 
 use crate::commands::*;
 
+use tauri::Manager;
+
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
@@ -29,7 +45,12 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .manage(state::AppState::new())
+        .setup(|app| {
+            // Settings are loaded by AppState::new, so the data directory has to be known first
+            settings::init_data_dir(app.path().app_data_dir()?);
+            app.manage(state::AppState::new());
+            Ok(())
+        })
                 .invoke_handler(tauri::generate_handler![
             commands::discover_models,
             commands::start_generation,
@@ -44,7 +65,30 @@ pub fn run() {
             commands::search_knowledge_base,
             commands::get_improvement_suggestions,
             commands::list_collections,
-            commands::generate_prompt_improvements
+            commands::generate_prompt_improvements,
+            commands::list_openai_compatible_endpoints,
+            commands::add_openai_compatible_endpoint,
+            commands::remove_openai_compatible_endpoint,
+            commands::get_ollama_hosts,
+            commands::set_ollama_hosts,
+            commands::get_configured_api_keys,
+            commands::set_provider_api_key,
+            commands::check_ollama_connection,
+            commands::get_pinned_openai_models,
+            commands::set_pinned_openai_models,
+            commands::get_run_summary,
+            commands::get_model_pricing,
+            commands::set_model_pricing,
+            commands::get_ollama_parallelism,
+            commands::set_ollama_parallelism,
+            commands::list_quarantined_responses,
+            commands::retry_quarantined_response,
+            commands::discard_quarantined_response,
+            commands::get_request_archive,
+            commands::get_request_archive_settings,
+            commands::set_request_archive_settings,
+            commands::get_response_cache_stats,
+            commands::clear_response_cache
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,1190 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::types::ModelProvider;
+use crate::structured_output::ResponseSchema;
+use crate::settings::{OpenAICompatibleEndpoint, ProviderSettings};
+use crate::rate_limiter::{RateLimitHeaders, RateLimitedError};
+
+pub const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+pub const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_API_VERSION: &str = "2023-06-01";
+/// The Messages API requires max_tokens; used when the caller does not set one
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 4096;
+/// Tokens generated per request when probing Ollama parallelism
+const PROBE_MAX_TOKENS: u32 = 48;
+/// Concurrent probe requests slower than this share of running them in series are taken as queued
+const PROBE_SERIAL_RATIO: f64 = 0.75;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// Provider-agnostic request used for both completion and chat calls
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompletionRequest {
+    pub model: String,
+    pub system: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// Sequences that end generation when produced
+    pub stop: Vec<String>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    /// Sampling seed, for backends that support reproducible sampling
+    pub seed: Option<u64>,
+    /// Constrain the output to this JSON Schema where the backend supports it
+    pub response_schema: Option<ResponseSchema>,
+}
+
+impl CompletionRequest {
+    /// Create a request with a single user prompt
+    pub fn new(model: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            messages: vec![ChatMessage::user(prompt)],
+            ..Default::default()
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_top_k(mut self, top_k: u32) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_response_schema(mut self, schema: ResponseSchema) -> Self {
+        self.response_schema = Some(schema);
+        self
+    }
+
+    /// Flatten the conversation into a single prompt for completion-style endpoints
+    pub fn prompt(&self) -> String {
+        self.messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResponse {
+    pub text: String,
+    pub model: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+    /// Backend configuration the response came from (OpenAI `system_fingerprint`), for reproducibility
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+    /// Rate-limit headers sent with the response; not kept in the response cache
+    #[serde(skip)]
+    pub rate_limit: Option<RateLimitHeaders>,
+    /// Served from the response cache, so no tokens were spent on it
+    #[serde(skip)]
+    pub cached: bool,
+}
+
+/// A model entry from an OpenAI-style /v1/models listing
+#[derive(Debug, Clone)]
+pub struct RemoteModel {
+    pub id: String,
+    /// Unix timestamp
+    pub created: Option<i64>,
+    pub owned_by: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub embedding: Vec<f32>,
+}
+
+/// Common interface implemented by every model backend
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// The provider family this backend talks to
+    fn kind(&self) -> ModelProvider;
+
+    /// Server the requests go to, so that identical requests to different servers stay apart
+    fn endpoint_url(&self) -> String {
+        String::new()
+    }
+
+    /// Single-prompt completion; backends without a dedicated endpoint use chat
+    async fn complete(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        self.chat(request).await
+    }
+
+    /// Multi-message chat completion
+    async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse>;
+
+    /// Completion delivered incrementally through `on_delta`; returns the full response at the end
+    ///
+    /// Backends without a streaming implementation deliver the whole text as one delta.
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<CompletionResponse> {
+        let response = self.complete(request).await?;
+        on_delta(&response.text);
+        Ok(response)
+    }
+
+    /// Text embedding
+    async fn embed(&self, request: &EmbeddingRequest) -> Result<EmbeddingResponse>;
+}
+
+/// Create the provider backend for a model provider
+///
+/// `endpoint` names the configured server for providers that can have several
+/// (currently OpenAI-compatible endpoints); it is ignored for the others.
+pub fn create_provider(
+    provider: &ModelProvider,
+    endpoint: Option<&str>,
+    settings: &ProviderSettings,
+    client: reqwest::Client,
+) -> Result<Arc<dyn LlmProvider>> {
+    match provider {
+        ModelProvider::Ollama => Ok(Arc::new(OllamaProvider::new(client, settings.ollama_base_url()))),
+        ModelProvider::OpenAI => Ok(Arc::new(OpenAIProvider::new(
+            client,
+            OPENAI_BASE_URL,
+            settings.api_key(provider),
+        ))),
+        ModelProvider::OpenAICompatible => {
+            let name = endpoint.ok_or_else(|| anyhow::anyhow!("No endpoint specified for OpenAI-compatible model"))?;
+            let endpoint = settings
+                .find_openai_compatible_endpoint(name)
+                .ok_or_else(|| anyhow::anyhow!("OpenAI-compatible endpoint '{}' is not configured", name))?;
+            Ok(Arc::new(OpenAIProvider::compatible(client, endpoint)))
+        }
+        ModelProvider::Anthropic => Ok(Arc::new(AnthropicProvider::new(
+            client,
+            settings.anthropic_base_url.as_deref().unwrap_or(ANTHROPIC_BASE_URL),
+            settings.api_key(provider),
+        ))),
+    }
+}
+
+/// Ollama backend using the native /api endpoints
+pub struct OllamaProvider {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl OllamaProvider {
+    pub fn new(client: reqwest::Client, base_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn build_options(request: &CompletionRequest) -> serde_json::Value {
+        let mut options = serde_json::Map::new();
+        if let Some(temperature) = request.temperature {
+            options.insert("temperature".to_string(), serde_json::json!(temperature));
+        }
+        if let Some(top_p) = request.top_p {
+            options.insert("top_p".to_string(), serde_json::json!(top_p));
+        }
+        if let Some(top_k) = request.top_k {
+            options.insert("top_k".to_string(), serde_json::json!(top_k));
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            options.insert("num_predict".to_string(), serde_json::json!(max_tokens));
+        }
+        if let Some(repeat_penalty) = request.repeat_penalty {
+            options.insert("repeat_penalty".to_string(), serde_json::json!(repeat_penalty));
+        }
+        if let Some(presence_penalty) = request.presence_penalty {
+            options.insert("presence_penalty".to_string(), serde_json::json!(presence_penalty));
+        }
+        if let Some(frequency_penalty) = request.frequency_penalty {
+            options.insert("frequency_penalty".to_string(), serde_json::json!(frequency_penalty));
+        }
+        if !request.stop.is_empty() {
+            options.insert("stop".to_string(), serde_json::json!(request.stop));
+        }
+        if let Some(seed) = request.seed {
+            options.insert("seed".to_string(), serde_json::json!(seed));
+        }
+        serde_json::Value::Object(options)
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Server version reported by /api/version
+    pub async fn version(&self) -> Result<String> {
+        let result = self.get("/api/version").await?;
+        Ok(result["version"].as_str().unwrap_or("unknown").to_string())
+    }
+
+    /// Estimate how many requests the server runs at once by timing concurrent generations
+    ///
+    /// Requests beyond the server's parallel slots queue, so a batch of them takes about
+    /// as long as running them one after another. Tries 2, 4, 8, ... up to `max`.
+    pub async fn probe_parallelism(&self, model: &str, max: usize) -> Result<usize> {
+        let request = CompletionRequest::new(model, "Count from 1 to 20.")
+            .with_temperature(0.0)
+            .with_max_tokens(PROBE_MAX_TOKENS);
+
+        // The first request may also load the model, so it only warms up
+        self.complete(&request).await?;
+        let started = std::time::Instant::now();
+        self.complete(&request).await?;
+        let single = started.elapsed().as_secs_f64();
+
+        let mut parallelism = 1;
+        let mut candidate = 2;
+        while candidate <= max {
+            let started = std::time::Instant::now();
+            futures::future::try_join_all((0..candidate).map(|_| self.complete(&request))).await?;
+            if started.elapsed().as_secs_f64() > single * candidate as f64 * PROBE_SERIAL_RATIO {
+                break;
+            }
+            parallelism = candidate;
+            candidate *= 2;
+        }
+        Ok(parallelism)
+    }
+
+    /// Names of the models pulled onto this server (/api/tags)
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let result = self.get("/api/tags").await?;
+        let empty_vec = vec![];
+        Ok(result["models"]
+            .as_array()
+            .unwrap_or(&empty_vec)
+            .iter()
+            .filter_map(|model| model["name"].as_str().map(String::from))
+            .collect())
+    }
+
+    /// Model metadata: details, model_info, template and capabilities (/api/show)
+    pub async fn show(&self, model: &str) -> Result<serde_json::Value> {
+        self.post("/api/show", &serde_json::json!({ "model": model })).await
+    }
+
+    /// Names of the models currently loaded into memory (/api/ps)
+    pub async fn running_models(&self) -> Result<Vec<String>> {
+        let result = self.get("/api/ps").await?;
+        let empty_vec = vec![];
+        Ok(result["models"]
+            .as_array()
+            .unwrap_or(&empty_vec)
+            .iter()
+            .filter_map(|model| model["name"].as_str().map(String::from))
+            .collect())
+    }
+
+    async fn get(&self, path: &str) -> Result<serde_json::Value> {
+        let response = self.client
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await?;
+        Self::into_json(response).await
+    }
+
+    async fn post(&self, path: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
+        let response = self.client
+            .post(format!("{}{}", self.base_url, path))
+            .json(body)
+            .send()
+            .await?;
+        Self::into_json(response).await
+    }
+
+    async fn into_json(response: reqwest::Response) -> Result<serde_json::Value> {
+        Ok(Self::check_status(response).await?.json().await?)
+    }
+
+    async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(error_for_status("Ollama", response).await)
+        }
+    }
+
+    /// Token counts from a final response; `prompt_eval_count` is omitted when the prompt was cached
+    fn usage(result: &serde_json::Value) -> Option<TokenUsage> {
+        let completion_tokens = result["eval_count"].as_u64()?;
+        Some(TokenUsage {
+            prompt_tokens: result["prompt_eval_count"].as_u64().unwrap_or(0),
+            completion_tokens,
+        })
+    }
+
+    fn generate_body(request: &CompletionRequest, stream: bool) -> serde_json::Value {
+        let mut request_body = serde_json::json!({
+            "model": request.model,
+            "prompt": request.prompt(),
+            "stream": stream,
+            "options": Self::build_options(request)
+        });
+        if let Some(system) = &request.system {
+            request_body["system"] = serde_json::json!(system);
+        }
+        if let Some(schema) = &request.response_schema {
+            request_body["format"] = schema.schema.clone();
+        }
+        request_body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn kind(&self) -> ModelProvider {
+        ModelProvider::Ollama
+    }
+
+    fn endpoint_url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let result = self.post("/api/generate", &Self::generate_body(request, false)).await?;
+
+        Ok(CompletionResponse {
+            text: result["response"].as_str().unwrap_or("").to_string(),
+            model: result["model"].as_str().unwrap_or(&request.model).to_string(),
+            finish_reason: result["done_reason"].as_str().map(String::from),
+            usage: Self::usage(&result),
+            system_fingerprint: None,
+            rate_limit: None,
+            cached: false,
+        })
+    }
+
+    async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(serde_json::json!({"role": "system", "content": system}));
+        }
+        for message in &request.messages {
+            messages.push(serde_json::json!(message));
+        }
+
+        let mut request_body = serde_json::json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": false,
+            "options": Self::build_options(request)
+        });
+        if let Some(schema) = &request.response_schema {
+            request_body["format"] = schema.schema.clone();
+        }
+
+        let result = self.post("/api/chat", &request_body).await?;
+
+        Ok(CompletionResponse {
+            text: result["message"]["content"].as_str().unwrap_or("").to_string(),
+            model: result["model"].as_str().unwrap_or(&request.model).to_string(),
+            finish_reason: result["done_reason"].as_str().map(String::from),
+            usage: Self::usage(&result),
+            system_fingerprint: None,
+            rate_limit: None,
+            cached: false,
+        })
+    }
+
+    /// /api/generate streams newline-delimited JSON objects, the last one carrying `done: true`
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<CompletionResponse> {
+        let response = self.client
+            .post(format!("{}/api/generate", self.base_url))
+            .json(&Self::generate_body(request, true))
+            .send()
+            .await?;
+        let response = Self::check_status(response).await?;
+
+        let mut completion = CompletionResponse {
+            text: String::new(),
+            model: request.model.clone(),
+            finish_reason: None,
+            usage: None,
+            system_fingerprint: None,
+            rate_limit: None,
+            cached: false,
+        };
+        read_lines(response, |line| {
+            let chunk: serde_json::Value = serde_json::from_str(line)?;
+            if let Some(error) = chunk["error"].as_str() {
+                return Err(anyhow::anyhow!("Ollama API error: {}", error));
+            }
+            if let Some(delta) = chunk["response"].as_str().filter(|delta| !delta.is_empty()) {
+                completion.text.push_str(delta);
+                on_delta(delta);
+            }
+            if chunk["done"].as_bool().unwrap_or(false) {
+                completion.finish_reason = chunk["done_reason"].as_str().map(String::from);
+                completion.usage = Self::usage(&chunk);
+                return Ok(false);
+            }
+            Ok(true)
+        })
+        .await?;
+
+        Ok(completion)
+    }
+
+    async fn embed(&self, request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+        let request_body = serde_json::json!({
+            "model": request.model,
+            "prompt": request.input
+        });
+
+        let result = self.post("/api/embeddings", &request_body).await?;
+        let embedding_array = result["embedding"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Invalid embedding response format"))?;
+
+        Ok(EmbeddingResponse {
+            embedding: parse_embedding(embedding_array)?,
+        })
+    }
+}
+
+/// Backend for the OpenAI /v1 API, used for OpenAI itself and for compatible servers
+pub struct OpenAIProvider {
+    client: reqwest::Client,
+    kind: ModelProvider,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAIProvider {
+    pub fn new(client: reqwest::Client, base_url: impl Into<String>, api_key: Option<String>) -> Self {
+        Self {
+            client,
+            kind: ModelProvider::OpenAI,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    /// Create a backend for a self-hosted OpenAI-compatible server
+    pub fn compatible(client: reqwest::Client, endpoint: &OpenAICompatibleEndpoint) -> Self {
+        Self {
+            client,
+            kind: ModelProvider::OpenAICompatible,
+            base_url: endpoint.base_url.trim_end_matches('/').to_string(),
+            api_key: endpoint.api_key.clone().filter(|key| !key.is_empty()),
+        }
+    }
+
+    /// List the models served by this endpoint via /v1/models
+    pub async fn list_models(&self) -> Result<Vec<RemoteModel>> {
+        let mut request = self.client.get(format!("{}/models", self.base_url));
+        if let Some(api_key) = self.authorization()? {
+            request = request.header("Authorization", api_key);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("{:?} API error: {} - {}", self.kind, status, error_text));
+        }
+
+        let result: serde_json::Value = response.json().await?;
+        let empty_vec = vec![];
+        Ok(result["data"]
+            .as_array()
+            .unwrap_or(&empty_vec)
+            .iter()
+            .filter_map(|model| {
+                Some(RemoteModel {
+                    id: model["id"].as_str()?.to_string(),
+                    created: model["created"].as_i64(),
+                    owned_by: model["owned_by"].as_str().map(String::from),
+                })
+            })
+            .collect())
+    }
+
+    /// Authorization header value; only OpenAI itself requires a key
+    fn authorization(&self) -> Result<Option<String>> {
+        match (&self.api_key, &self.kind) {
+            (Some(api_key), _) => Ok(Some(format!("Bearer {}", api_key))),
+            (None, ModelProvider::OpenAI) => Err(anyhow::anyhow!(
+                "No OpenAI API key configured. Add one in the provider settings or set OPENAI_API_KEY"
+            )),
+            (None, _) => Ok(None),
+        }
+    }
+
+    async fn post(&self, path: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
+        Ok(self.send(path, body).await?.json().await?)
+    }
+
+    async fn send(&self, path: &str, body: &serde_json::Value) -> Result<reqwest::Response> {
+        let mut request = self.client
+            .post(format!("{}{}", self.base_url, path))
+            .header("Content-Type", "application/json")
+            .json(body);
+        if let Some(api_key) = self.authorization()? {
+            request = request.header("Authorization", api_key);
+        }
+
+        let response = request.send().await?;
+
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(error_for_status(&format!("{:?}", self.kind), response).await)
+        }
+    }
+
+    fn usage(result: &serde_json::Value) -> Option<TokenUsage> {
+        let usage = result.get("usage").filter(|usage| usage.is_object())?;
+        Some(TokenUsage {
+            prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        })
+    }
+
+    /// o-series and GPT-5 models, which take max_completion_tokens and reject sampling parameters
+    pub fn is_reasoning_model(model: &str) -> bool {
+        let base_model = model.strip_prefix("ft:").unwrap_or(model);
+        ["o1", "o3", "o4", "gpt-5"].iter().any(|prefix| base_model.starts_with(prefix))
+    }
+
+    fn chat_body(&self, request: &CompletionRequest) -> serde_json::Value {
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(serde_json::json!({"role": "system", "content": system}));
+        }
+        for message in &request.messages {
+            messages.push(serde_json::json!(message));
+        }
+
+        let mut request_body = serde_json::json!({
+            "model": request.model,
+            "messages": messages
+        });
+        let reasoning = matches!(self.kind, ModelProvider::OpenAI) && Self::is_reasoning_model(&request.model);
+        if let Some(max_tokens) = request.max_tokens {
+            let key = if reasoning { "max_completion_tokens" } else { "max_tokens" };
+            request_body[key] = serde_json::json!(max_tokens);
+        }
+        if let Some(seed) = request.seed {
+            request_body["seed"] = serde_json::json!(seed);
+        }
+        if !request.stop.is_empty() {
+            request_body["stop"] = serde_json::json!(request.stop);
+        }
+        if !reasoning {
+            if let Some(temperature) = request.temperature {
+                request_body["temperature"] = serde_json::json!(temperature);
+            }
+            if let Some(top_p) = request.top_p {
+                request_body["top_p"] = serde_json::json!(top_p);
+            }
+            if let Some(presence_penalty) = request.presence_penalty {
+                request_body["presence_penalty"] = serde_json::json!(presence_penalty);
+            }
+            if let Some(frequency_penalty) = request.frequency_penalty {
+                request_body["frequency_penalty"] = serde_json::json!(frequency_penalty);
+            }
+        }
+        // vLLM, llama.cpp and LocalAI accept top_k and repetition_penalty as extensions; OpenAI rejects them
+        if let ModelProvider::OpenAICompatible = self.kind {
+            if let Some(top_k) = request.top_k {
+                request_body["top_k"] = serde_json::json!(top_k);
+            }
+            if let Some(repeat_penalty) = request.repeat_penalty {
+                request_body["repetition_penalty"] = serde_json::json!(repeat_penalty);
+            }
+        }
+        if let Some(schema) = &request.response_schema {
+            request_body["response_format"] = serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": schema.name,
+                    "schema": schema.schema,
+                    "strict": true
+                }
+            });
+        }
+        request_body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAIProvider {
+    fn kind(&self) -> ModelProvider {
+        self.kind.clone()
+    }
+
+    fn endpoint_url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let response = self.send("/chat/completions", &self.chat_body(request)).await?;
+        let rate_limit = RateLimitHeaders::from_headers(response.headers());
+        let result: serde_json::Value = response.json().await?;
+
+        Ok(CompletionResponse {
+            text: result["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string(),
+            model: result["model"].as_str().unwrap_or(&request.model).to_string(),
+            finish_reason: result["choices"][0]["finish_reason"].as_str().map(String::from),
+            usage: Self::usage(&result),
+            system_fingerprint: result["system_fingerprint"].as_str().map(String::from),
+            rate_limit,
+            cached: false,
+        })
+    }
+
+    /// Chat completion streamed as server-sent `chat.completion.chunk` events
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<CompletionResponse> {
+        let mut request_body = self.chat_body(request);
+        request_body["stream"] = serde_json::json!(true);
+        // OpenAI only reports usage on streams when asked to, in a final chunk without choices
+        if self.kind == ModelProvider::OpenAI {
+            request_body["stream_options"] = serde_json::json!({"include_usage": true});
+        }
+        let response = self.send("/chat/completions", &request_body).await?;
+
+        let mut completion = CompletionResponse {
+            text: String::new(),
+            model: request.model.clone(),
+            finish_reason: None,
+            usage: None,
+            system_fingerprint: None,
+            rate_limit: RateLimitHeaders::from_headers(response.headers()),
+            cached: false,
+        };
+        read_lines(response, |line| {
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                return Ok(true);
+            };
+            if data == "[DONE]" {
+                return Ok(false);
+            }
+
+            let chunk: serde_json::Value = serde_json::from_str(data)?;
+            if let Some(model) = chunk["model"].as_str() {
+                completion.model = model.to_string();
+            }
+            if let Some(system_fingerprint) = chunk["system_fingerprint"].as_str() {
+                completion.system_fingerprint = Some(system_fingerprint.to_string());
+            }
+            if let Some(delta) = chunk["choices"][0]["delta"]["content"].as_str().filter(|delta| !delta.is_empty()) {
+                completion.text.push_str(delta);
+                on_delta(delta);
+            }
+            if let Some(finish_reason) = chunk["choices"][0]["finish_reason"].as_str() {
+                completion.finish_reason = Some(finish_reason.to_string());
+            }
+            if let Some(usage) = Self::usage(&chunk) {
+                completion.usage = Some(usage);
+            }
+            Ok(true)
+        })
+        .await?;
+
+        Ok(completion)
+    }
+
+    async fn embed(&self, request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+        let request_body = serde_json::json!({
+            "model": request.model,
+            "input": request.input
+        });
+
+        let result = self.post("/embeddings", &request_body).await?;
+        let embedding_array = result["data"][0]["embedding"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Invalid embedding response format"))?;
+
+        Ok(EmbeddingResponse {
+            embedding: parse_embedding(embedding_array)?,
+        })
+    }
+}
+
+/// Anthropic backend using the Messages API
+pub struct AnthropicProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl AnthropicProvider {
+    pub fn new(client: reqwest::Client, base_url: impl Into<String>, api_key: Option<String>) -> Self {
+        Self {
+            client,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    fn api_key(&self) -> Result<&str> {
+        self.api_key.as_deref().ok_or_else(|| anyhow::anyhow!(
+            "No Anthropic API key configured. Add one in the provider settings or set ANTHROPIC_API_KEY"
+        ))
+    }
+
+    /// List the (id, display name) pairs available to this API key via /v1/models
+    pub async fn list_models(&self) -> Result<Vec<(String, String)>> {
+        let response = self.client
+            .get(format!("{}/v1/models", self.base_url))
+            .header("x-api-key", self.api_key()?)
+            .header("anthropic-version", ANTHROPIC_API_VERSION)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Anthropic API error: {} - {}", status, error_text));
+        }
+
+        let result: serde_json::Value = response.json().await?;
+        let empty_vec = vec![];
+        Ok(result["data"]
+            .as_array()
+            .unwrap_or(&empty_vec)
+            .iter()
+            .filter_map(|model| {
+                let id = model["id"].as_str()?;
+                let display_name = model["display_name"].as_str().unwrap_or(id);
+                Some((id.to_string(), display_name.to_string()))
+            })
+            .collect())
+    }
+
+    async fn send(&self, body: &serde_json::Value) -> Result<reqwest::Response> {
+        let response = self.client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", self.api_key()?)
+            .header("anthropic-version", ANTHROPIC_API_VERSION)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_for_status("Anthropic", response).await);
+        }
+        Ok(response)
+    }
+
+    /// Build a Messages API body; system-role messages are folded into the top-level system prompt
+    ///
+    /// The Messages API has no schema-constrained decoding, so a response schema is passed as an instruction.
+    fn build_request_body(request: &CompletionRequest) -> serde_json::Value {
+        let schema_instruction = request.response_schema.as_ref().map(|schema| {
+            format!("Respond only with JSON matching this JSON Schema:\n{}", schema.schema)
+        });
+        let mut system_parts: Vec<&str> = request.system.iter().map(String::as_str).collect();
+        system_parts.extend(schema_instruction.as_deref());
+        let mut messages = Vec::new();
+        for message in &request.messages {
+            if message.role == "system" {
+                system_parts.push(&message.content);
+            } else {
+                messages.push(serde_json::json!(message));
+            }
+        }
+
+        let mut request_body = serde_json::json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
+            "messages": messages
+        });
+        if !system_parts.is_empty() {
+            request_body["system"] = serde_json::json!(system_parts.join("\n\n"));
+        }
+        // The Messages API accepts temperatures from 0 to 1, unlike the 0 to 2 range of OpenAI
+        if let Some(temperature) = request.temperature {
+            request_body["temperature"] = serde_json::json!(temperature.clamp(0.0, 1.0));
+        }
+        // Newer Claude models reject temperature and top_p together, so temperature wins
+        if let (Some(top_p), None) = (request.top_p, request.temperature) {
+            request_body["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(top_k) = request.top_k {
+            request_body["top_k"] = serde_json::json!(top_k);
+        }
+        if !request.stop.is_empty() {
+            request_body["stop_sequences"] = serde_json::json!(request.stop);
+        }
+        request_body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    fn kind(&self) -> ModelProvider {
+        ModelProvider::Anthropic
+    }
+
+    fn endpoint_url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let response = self.send(&Self::build_request_body(request)).await?;
+        let rate_limit = RateLimitHeaders::from_headers(response.headers());
+        let result: serde_json::Value = response.json().await?;
+        let empty_vec = vec![];
+        let text = result["content"]
+            .as_array()
+            .unwrap_or(&empty_vec)
+            .iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("");
+
+        let usage = result.get("usage").map(|usage| TokenUsage {
+            prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+        });
+
+        Ok(CompletionResponse {
+            text,
+            model: result["model"].as_str().unwrap_or(&request.model).to_string(),
+            finish_reason: result["stop_reason"].as_str().map(String::from),
+            usage,
+            system_fingerprint: None,
+            rate_limit,
+            cached: false,
+        })
+    }
+
+    /// Messages API streaming: text arrives in `content_block_delta` events, the stop reason in `message_delta`
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<CompletionResponse> {
+        let mut request_body = Self::build_request_body(request);
+        request_body["stream"] = serde_json::json!(true);
+        let response = self.send(&request_body).await?;
+
+        let mut completion = CompletionResponse {
+            text: String::new(),
+            model: request.model.clone(),
+            finish_reason: None,
+            usage: None,
+            system_fingerprint: None,
+            rate_limit: RateLimitHeaders::from_headers(response.headers()),
+            cached: false,
+        };
+        let mut usage = TokenUsage::default();
+        read_lines(response, |line| {
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                return Ok(true);
+            };
+
+            let event: serde_json::Value = serde_json::from_str(data)?;
+            match event["type"].as_str().unwrap_or_default() {
+                "message_start" => {
+                    if let Some(model) = event["message"]["model"].as_str() {
+                        completion.model = model.to_string();
+                    }
+                    usage.prompt_tokens = event["message"]["usage"]["input_tokens"].as_u64().unwrap_or(0);
+                }
+                "content_block_delta" => {
+                    if let Some(delta) = event["delta"]["text"].as_str() {
+                        completion.text.push_str(delta);
+                        on_delta(delta);
+                    }
+                }
+                "message_delta" => {
+                    completion.finish_reason = event["delta"]["stop_reason"].as_str().map(String::from);
+                    usage.completion_tokens = event["usage"]["output_tokens"].as_u64().unwrap_or(0);
+                }
+                "message_stop" => return Ok(false),
+                "error" => {
+                    return Err(anyhow::anyhow!("Anthropic API error: {}", event["error"]["message"]));
+                }
+                _ => {}
+            }
+            Ok(true)
+        })
+        .await?;
+
+        completion.usage = Some(usage);
+        Ok(completion)
+    }
+
+    async fn embed(&self, _request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+        Err(anyhow::anyhow!("Anthropic does not provide an embeddings API"))
+    }
+}
+
+/// A non-success HTTP status from a provider API
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Turn a failed response into an error, keeping Retry-After on a 429 so callers can back off
+async fn error_for_status(service: &str, response: reqwest::Response) -> anyhow::Error {
+    let status = response.status();
+    let retry_after = RateLimitHeaders::from_headers(response.headers()).and_then(|headers| headers.retry_after);
+    let error_text = response.text().await.unwrap_or_default();
+    tracing::error!("{} API error: {} - {}", service, status, error_text);
+
+    let message = format!("{} API error: {} - {}", service, status, error_text);
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        RateLimitedError { retry_after, message }.into()
+    } else {
+        ApiError { status: status.as_u16(), message }.into()
+    }
+}
+
+/// Feed each complete line of a streaming response body to `on_line` until it returns false
+async fn read_lines(
+    mut response: reqwest::Response,
+    mut on_line: impl FnMut(&str) -> Result<bool>,
+) -> Result<()> {
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+        while let Some(newline) = buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if !line.is_empty() && !on_line(line)? {
+                return Ok(());
+            }
+        }
+    }
+
+    let line = String::from_utf8_lossy(&buffer);
+    let line = line.trim();
+    if !line.is_empty() {
+        on_line(line)?;
+    }
+    Ok(())
+}
+
+fn parse_embedding(values: &[serde_json::Value]) -> Result<Vec<f32>> {
+    values
+        .iter()
+        .map(|v| v.as_f64().map(|f| f as f32).ok_or_else(|| anyhow::anyhow!("Invalid embedding value")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve a single canned HTTP response and hand back the raw request that was received
+    async fn mock_server(status: u16, body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..header_end]
+                        .lines()
+                        .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+
+            let response = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        (base_url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_chat_parses_messages_response() {
+        let (base_url, server) = mock_server(200, r#"{
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-test",
+            "content": [{"type": "text", "text": "[{\"instruction\": "}, {"type": "text", "text": "\"hi\"}]"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 12, "output_tokens": 7}
+        }"#).await;
+
+        let provider = AnthropicProvider::new(reqwest::Client::new(), base_url, Some("test-key".to_string()));
+        let request = CompletionRequest::new("claude-test", "Generate one example")
+            .with_system("You write datasets")
+            .with_temperature(0.7)
+            .with_top_p(0.9);
+        let response = provider.chat(&request).await.unwrap();
+
+        assert_eq!(response.text, "[{\"instruction\": \"hi\"}]");
+        assert_eq!(response.finish_reason.as_deref(), Some("end_turn"));
+        assert_eq!(response.usage, Some(TokenUsage { prompt_tokens: 12, completion_tokens: 7 }));
+
+        let raw_request = server.await.unwrap();
+        assert!(raw_request.starts_with("POST /v1/messages"));
+        assert!(raw_request.contains("x-api-key: test-key"));
+        assert!(raw_request.contains(&format!("anthropic-version: {}", ANTHROPIC_API_VERSION)));
+
+        let body: serde_json::Value = serde_json::from_str(raw_request.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["system"], "You write datasets");
+        assert_eq!(body["max_tokens"], ANTHROPIC_DEFAULT_MAX_TOKENS);
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert!(body.get("top_p").is_none());
+        assert_eq!(AnthropicProvider::build_request_body(&request.with_temperature(1.6))["temperature"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_reports_api_errors() {
+        let (base_url, _server) = mock_server(400, r#"{"type": "error", "error": {"type": "invalid_request_error", "message": "bad"}}"#).await;
+
+        let provider = AnthropicProvider::new(reqwest::Client::new(), base_url, Some("test-key".to_string()));
+        let error = provider.chat(&CompletionRequest::new("claude-test", "hi")).await.unwrap_err();
+
+        assert!(error.to_string().contains("400"));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_requires_api_key() {
+        let provider = AnthropicProvider::new(reqwest::Client::new(), "http://127.0.0.1:9", None);
+        let error = provider.chat(&CompletionRequest::new("claude-test", "hi")).await.unwrap_err();
+
+        assert!(error.to_string().contains("ANTHROPIC_API_KEY"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_sends_seed_and_reads_fingerprint() {
+        let (base_url, server) = mock_server(200, r#"{
+            "model": "gpt-4o-mini",
+            "system_fingerprint": "fp_44709d6fcb",
+            "choices": [{"message": {"role": "assistant", "content": "[]"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 9, "completion_tokens": 1}
+        }"#).await;
+
+        let provider = OpenAIProvider::new(reqwest::Client::new(), base_url, Some("test-key".to_string()));
+        let response = provider.chat(&CompletionRequest::new("gpt-4o-mini", "hi").with_seed(42)).await.unwrap();
+        assert_eq!(response.system_fingerprint.as_deref(), Some("fp_44709d6fcb"));
+
+        let raw_request = server.await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(raw_request.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["seed"], 42);
+    }
+
+    #[test]
+    fn test_openai_reasoning_models_get_completion_token_limit_without_sampling() {
+        let provider = OpenAIProvider::new(reqwest::Client::new(), OPENAI_BASE_URL, Some("test-key".to_string()));
+        let request = |model| CompletionRequest::new(model, "hi").with_temperature(0.7).with_top_p(0.9).with_max_tokens(1000);
+
+        let body = provider.chat_body(&request("o4-mini"));
+        assert_eq!(body["max_completion_tokens"], 1000);
+        assert!(body.get("max_tokens").is_none() && body.get("temperature").is_none() && body.get("top_p").is_none());
+
+        let body = provider.chat_body(&request("gpt-4o-mini"));
+        assert_eq!(body["max_tokens"], 1000);
+        assert!(body.get("temperature").is_some() && body.get("max_completion_tokens").is_none());
+    }
+}
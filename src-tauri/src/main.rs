@@ -2,12 +2,26 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod types;
+mod usage;
 mod models;
+mod model_mix;
+mod ollama_pool;
 mod dataset;
 mod dataset_concurrent;
+mod json_recovery;
+mod llm_provider;
+mod settings;
+mod structured_output;
 mod state;
 mod commands;
+mod concurrency;
 mod quality_validator;
+mod quarantine;
+mod rate_limiter;
+mod request_archive;
+mod response_cache;
+mod retry;
+mod sampling;
 mod embedding_service;
 mod vector_db;
 mod knowledge_base;
@@ -16,7 +30,7 @@ mod chromadb_server;
 
 use state::AppState;
 use tauri::Manager;
-use commands::{discover_models, start_generation, cancel_generation, get_progress, export_dataset, debug_dataset_state, improve_prompt, generate_use_case_suggestions, start_chromadb_server, stop_chromadb_server, get_chromadb_server_status, check_chromadb_available};
+use commands::{discover_models, start_generation, cancel_generation, get_progress, export_dataset, debug_dataset_state, improve_prompt, generate_use_case_suggestions, start_chromadb_server, stop_chromadb_server, get_chromadb_server_status, check_chromadb_available, list_openai_compatible_endpoints, add_openai_compatible_endpoint, remove_openai_compatible_endpoint, get_ollama_hosts, set_ollama_hosts, get_configured_api_keys, set_provider_api_key, check_ollama_connection, get_pinned_openai_models, set_pinned_openai_models, get_run_summary, get_model_pricing, set_model_pricing, get_ollama_parallelism, set_ollama_parallelism, get_response_cache_stats, clear_response_cache, list_quarantined_responses, retry_quarantined_response, discard_quarantined_response, get_request_archive, get_request_archive_settings, set_request_archive_settings};
 
 async fn setup_chromadb(app_handle: tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     let state = app_handle.state::<AppState>();
@@ -55,8 +69,10 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .manage(AppState::new())
         .setup(|app| {
+            // Settings are loaded by AppState::new, so the data directory has to be known first
+            settings::init_data_dir(app.path().app_data_dir()?);
+            app.manage(AppState::new());
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 if let Err(e) = setup_chromadb(handle).await {
@@ -77,7 +93,30 @@ fn main() {
             start_chromadb_server,
             stop_chromadb_server,
             get_chromadb_server_status,
-            check_chromadb_available
+            check_chromadb_available,
+            list_openai_compatible_endpoints,
+            add_openai_compatible_endpoint,
+            remove_openai_compatible_endpoint,
+            get_ollama_hosts,
+            set_ollama_hosts,
+            get_configured_api_keys,
+            set_provider_api_key,
+            check_ollama_connection,
+            get_pinned_openai_models,
+            set_pinned_openai_models,
+            get_run_summary,
+            get_model_pricing,
+            set_model_pricing,
+            get_ollama_parallelism,
+            set_ollama_parallelism,
+            list_quarantined_responses,
+            retry_quarantined_response,
+            discard_quarantined_response,
+            get_request_archive,
+            get_request_archive_settings,
+            set_request_archive_settings,
+            get_response_cache_stats,
+            clear_response_cache
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,64 @@
+use anyhow::Result;
+
+use crate::types::{Model, ModelWeight};
+
+/// Resolve a weighted model mix against the discovered models
+pub fn resolve_mix(mix: &[ModelWeight], models: &[Model]) -> Result<Vec<(Model, f32)>> {
+    if mix.is_empty() {
+        return Err(anyhow::anyhow!("No models selected"));
+    }
+
+    mix.iter()
+        .map(|entry| {
+            if !entry.weight.is_finite() || entry.weight <= 0.0 {
+                return Err(anyhow::anyhow!("Model {} needs a positive weight, got {}", entry.model_id, entry.weight));
+            }
+            let model = models
+                .iter()
+                .find(|model| model.id == entry.model_id)
+                .ok_or_else(|| anyhow::anyhow!("Selected model {} not found", entry.model_id))?;
+            Ok((model.clone(), entry.weight))
+        })
+        .collect()
+}
+
+/// Pick a choice for each of `batches` batches, in proportion to `weights`
+///
+/// Uses smooth weighted round-robin, so the choices are interleaved rather than
+/// run in blocks and every prefix of the schedule stays close to the weights.
+pub fn assign_batches(weights: &[f32], batches: usize) -> Vec<usize> {
+    let total: f32 = weights.iter().sum();
+    let mut current = vec![0.0f32; weights.len()];
+
+    (0..batches)
+        .map(|_| {
+            for (current, weight) in current.iter_mut().zip(weights) {
+                *current += weight;
+            }
+            let chosen = current
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1).then(b.0.cmp(&a.0)))
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+            current[chosen] -= total;
+            chosen
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_follow_weights_and_interleave() {
+        let assignment = assign_batches(&[0.6, 0.3, 0.1], 10);
+        let count = |choice| assignment.iter().filter(|&&chosen| chosen == choice).count();
+        assert_eq!((count(0), count(1), count(2)), (6, 3, 1));
+        assert_eq!(&assignment[..3], &[0, 1, 0]);
+
+        assert_eq!(assign_batches(&[1.0], 3), vec![0, 0, 0]);
+        assert!(assign_batches(&[1.0, 1.0], 0).is_empty());
+    }
+}
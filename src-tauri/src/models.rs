@@ -1,14 +1,18 @@
-use crate::types::{Model, ModelProvider};
+use crate::types::{Model, ModelDetails, ModelProvider, OllamaConnectionStatus};
+use crate::llm_provider::{AnthropicProvider, OllamaProvider, OpenAIProvider, ANTHROPIC_BASE_URL, OPENAI_BASE_URL};
+use crate::settings::{data_dir, OpenAICompatibleEndpoint, ProviderSettings};
+
+const OPENAI_MODEL_CACHE_FILE: &str = "openai_models.json";
 
 pub struct ModelManager;
 
 impl ModelManager {
-    pub async fn discover_ollama_models() -> anyhow::Result<Vec<Model>> {
+    pub async fn discover_ollama_models(base_url: &str) -> anyhow::Result<Vec<Model>> {
         let client = reqwest::Client::new();
         
         // Ollama API endpoint for listing models
         let response = client
-            .get("http://localhost:11434/api/tags")
+            .get(format!("{}/api/tags", base_url))
             .send()
             .await?;
         
@@ -17,25 +21,222 @@ impl ModelManager {
             let empty_vec = vec![];
             let models = ollama_response["models"].as_array().unwrap_or(&empty_vec);
             
+            let provider = OllamaProvider::new(client, base_url);
+            let probes = models.iter().map(|model| {
+                let name = model["name"].as_str().unwrap_or("unknown").to_string();
+                let provider = &provider;
+                async move {
+                    let show = provider.show(&name).await;
+                    (name, show)
+                }
+            });
+            let probed = futures::future::join_all(probes).await;
+            
             let mut discovered_models = Vec::new();
-            for model in models {
+            for (model, (name, show)) in models.iter().zip(probed) {
+                let mut details = ModelDetails {
+                    size_bytes: model["size"].as_u64(),
+                    quantization: model["details"]["quantization_level"].as_str().map(String::from),
+                    family: model["details"]["family"].as_str().map(String::from),
+                    ..ModelDetails::default()
+                };
+                let mut capabilities = vec!["text-generation".to_string()];
+                
+                match show {
+                    Ok(show) => capabilities = Self::apply_ollama_show(&show, &mut details),
+                    Err(e) => tracing::warn!("Could not probe Ollama model {}: {}", name, e),
+                }
+                
                 discovered_models.push(Model {
-                    id: model["name"].as_str().unwrap_or("unknown").to_string(),
-                    name: model["name"].as_str().unwrap_or("unknown").to_string(),
-                    size: model["size"].as_str().unwrap_or("unknown").to_string(),
+                    id: name.clone(),
+                    name,
+                    size: details.size_bytes.map(format_bytes).unwrap_or_else(|| "unknown".to_string()),
                     modified: model["modified_at"].as_str().unwrap_or("unknown").to_string(),
                     provider: ModelProvider::Ollama,
-                    capabilities: vec!["text-generation".to_string()],
+                    capabilities,
+                    endpoint: None,
+                    details,
                 });
             }
             
             Ok(discovered_models)
         } else {
-            Err(anyhow::anyhow!("Failed to connect to Ollama service"))
+            Err(anyhow::anyhow!("Failed to connect to Ollama service at {}", base_url))
+        }
+    }
+
+    /// Fill in details from an /api/show response and return the model's capabilities
+    fn apply_ollama_show(show: &serde_json::Value, details: &mut ModelDetails) -> Vec<String> {
+        let model_info = &show["model_info"];
+        let architecture = model_info["general.architecture"].as_str().unwrap_or_default();
+        
+        details.context_length = model_info[format!("{}.context_length", architecture)].as_u64();
+        details.parameter_count = model_info["general.parameter_count"].as_u64();
+        details.template = show["template"].as_str().map(String::from);
+        if let Some(quantization) = show["details"]["quantization_level"].as_str() {
+            details.quantization = Some(quantization.to_string());
+        }
+        if let Some(family) = show["details"]["family"].as_str() {
+            details.family = Some(family.to_string());
+        }
+        
+        // Newer Ollama versions report capabilities directly; "completion" is our text generation
+        match show["capabilities"].as_array() {
+            Some(reported) => reported
+                .iter()
+                .filter_map(|capability| capability.as_str())
+                .map(|capability| match capability {
+                    "completion" => "text-generation".to_string(),
+                    other => other.to_string(),
+                })
+                .collect(),
+            None => vec!["text-generation".to_string()],
+        }
+    }
+
+    /// Check that an Ollama server is reachable and report its version and loaded models
+    pub async fn check_ollama_connection(base_url: &str) -> OllamaConnectionStatus {
+        let provider = OllamaProvider::new(
+            reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            base_url,
+        );
+
+        let result = async {
+            let version = provider.version().await?;
+            let loaded_models = provider.running_models().await?;
+            anyhow::Ok((version, loaded_models))
+        }
+        .await;
+
+        match result {
+            Ok((version, loaded_models)) => OllamaConnectionStatus {
+                base_url: provider.base_url().to_string(),
+                reachable: true,
+                version: Some(version),
+                loaded_models,
+                error: None,
+            },
+            Err(e) => OllamaConnectionStatus {
+                base_url: provider.base_url().to_string(),
+                reachable: false,
+                version: None,
+                loaded_models: Vec::new(),
+                error: Some(e.to_string()),
+            },
         }
     }
     
-    pub async fn get_openai_models() -> anyhow::Result<Vec<Model>> {
+    /// List OpenAI chat models via /v1/models, merged with the user's pinned models
+    ///
+    /// Falls back to the last successful listing when the API is unreachable or no key is set.
+    pub async fn get_openai_models(settings: &ProviderSettings) -> anyhow::Result<Vec<Model>> {
+        let mut models = match settings.api_key(&ModelProvider::OpenAI) {
+            Some(api_key) => match Self::discover_openai_models(api_key).await {
+                Ok(models) => {
+                    if let Err(e) = Self::save_openai_model_cache(&models) {
+                        tracing::warn!("Could not cache OpenAI model list: {}", e);
+                    }
+                    models
+                }
+                Err(e) => {
+                    tracing::warn!("OpenAI model discovery failed, using cached list: {}", e);
+                    Self::cached_openai_models()
+                }
+            },
+            None => Self::cached_openai_models(),
+        };
+
+        for pinned in &settings.pinned_openai_models {
+            match models.iter_mut().find(|model| &model.id == pinned) {
+                Some(model) => model.capabilities.push("pinned".to_string()),
+                None => {
+                    let mut model = Self::openai_model(pinned.clone(), None, None);
+                    model.capabilities.push("pinned".to_string());
+                    models.push(model);
+                }
+            }
+        }
+
+        Ok(models)
+    }
+
+    async fn discover_openai_models(api_key: String) -> anyhow::Result<Vec<Model>> {
+        let provider = OpenAIProvider::new(reqwest::Client::new(), OPENAI_BASE_URL, Some(api_key));
+        let mut models: Vec<Model> = provider
+            .list_models()
+            .await?
+            .into_iter()
+            .filter(|model| Self::is_openai_chat_model(&model.id))
+            .map(|model| Self::openai_model(model.id, model.created, model.owned_by))
+            .collect();
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(models)
+    }
+
+    /// /v1/models also lists embedding, audio, image and moderation models
+    fn is_openai_chat_model(id: &str) -> bool {
+        const CHAT_PREFIXES: [&str; 6] = ["gpt-", "o1", "o3", "o4", "chatgpt-", "ft:gpt-"];
+        const NON_CHAT_MARKERS: [&str; 9] = [
+            "embedding", "whisper", "tts", "dall-e", "audio", "realtime", "transcribe", "image", "instruct",
+        ];
+
+        CHAT_PREFIXES.iter().any(|prefix| id.starts_with(prefix))
+            && !NON_CHAT_MARKERS.iter().any(|marker| id.contains(marker))
+    }
+
+    fn openai_model(id: String, created: Option<i64>, owned_by: Option<String>) -> Model {
+        let base_id = id.strip_prefix("ft:").unwrap_or(&id);
+        let mut capabilities = vec!["text-generation".to_string(), "instruction-following".to_string()];
+        if OpenAIProvider::is_reasoning_model(&id) {
+            capabilities.push("reasoning".to_string());
+        }
+        if ["gpt-4o", "gpt-4.1", "gpt-4-turbo", "gpt-5"].iter().any(|prefix| base_id.starts_with(prefix)) {
+            capabilities.push("multimodal".to_string());
+        }
+        if id.contains("mini") || id.contains("nano") {
+            capabilities.push("fast-inference".to_string());
+        }
+        if id.starts_with("ft:") {
+            capabilities.push("fine-tuned".to_string());
+        }
+        if let Some(owner) = owned_by {
+            capabilities.push(format!("owned-by:{}", owner));
+        }
+
+        Model {
+            name: id.clone(),
+            id,
+            size: "hosted".to_string(),
+            modified: created
+                .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            provider: ModelProvider::OpenAI,
+            capabilities,
+            endpoint: None,
+            details: ModelDetails::default(),
+        }
+    }
+
+    fn cached_openai_models() -> Vec<Model> {
+        std::fs::read_to_string(data_dir().join(OPENAI_MODEL_CACHE_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_else(Self::default_openai_models)
+    }
+
+    fn save_openai_model_cache(models: &[Model]) -> anyhow::Result<()> {
+        let dir = data_dir();
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(OPENAI_MODEL_CACHE_FILE), serde_json::to_string(models)?)?;
+        Ok(())
+    }
+
+    /// Models offered when live discovery has never succeeded
+    fn default_openai_models() -> Vec<Model> {
         // OpenAI models we'll support (latest models as of 2025)
         let openai_models = vec![
             Model {
@@ -45,6 +246,8 @@ impl ModelManager {
                 modified: "2025".to_string(),
                 provider: ModelProvider::OpenAI,
                 capabilities: vec!["text-generation".to_string(), "instruction-following".to_string(), "fast-inference".to_string()],
+                endpoint: None,
+                details: ModelDetails::default(),
             },
             Model {
                 id: "gpt-4o".to_string(),
@@ -53,6 +256,8 @@ impl ModelManager {
                 modified: "2024".to_string(),
                 provider: ModelProvider::OpenAI,
                 capabilities: vec!["text-generation".to_string(), "instruction-following".to_string(), "multimodal".to_string()],
+                endpoint: None,
+                details: ModelDetails::default(),
             },
             Model {
                 id: "gpt-4o-mini".to_string(),
@@ -61,6 +266,8 @@ impl ModelManager {
                 modified: "2024".to_string(),
                 provider: ModelProvider::OpenAI,
                 capabilities: vec!["text-generation".to_string(), "instruction-following".to_string(), "fast-inference".to_string()],
+                endpoint: None,
+                details: ModelDetails::default(),
             },
             Model {
                 id: "gpt-4.1-mini".to_string(),
@@ -69,9 +276,106 @@ impl ModelManager {
                 modified: "2025".to_string(),
                 provider: ModelProvider::OpenAI,
                 capabilities: vec!["text-generation".to_string(), "instruction-following".to_string(), "enhanced-reasoning".to_string()],
+                endpoint: None,
+                details: ModelDetails::default(),
             },
         ];
         
-        Ok(openai_models)
+        openai_models
+    }
+
+    /// Discover the models served by an OpenAI-compatible endpoint via /v1/models
+    pub async fn discover_openai_compatible_models(endpoint: &OpenAICompatibleEndpoint) -> anyhow::Result<Vec<Model>> {
+        let provider = OpenAIProvider::compatible(reqwest::Client::new(), endpoint);
+        let remote_models = provider.list_models().await?;
+
+        Ok(remote_models
+            .into_iter()
+            .map(|remote| Model {
+                id: format!("{}/{}", endpoint.name, remote.id),
+                name: remote.id,
+                size: "unknown".to_string(),
+                modified: "unknown".to_string(),
+                provider: ModelProvider::OpenAICompatible,
+                capabilities: vec!["text-generation".to_string()],
+                endpoint: Some(endpoint.name.clone()),
+                details: ModelDetails::default(),
+            })
+            .collect())
+    }
+
+    /// List the Claude models available to the configured Anthropic API key
+    pub async fn discover_anthropic_models(settings: &ProviderSettings) -> anyhow::Result<Vec<Model>> {
+        let provider = AnthropicProvider::new(
+            reqwest::Client::new(),
+            settings.anthropic_base_url.as_deref().unwrap_or(ANTHROPIC_BASE_URL),
+            settings.api_key(&ModelProvider::Anthropic),
+        );
+        let models = provider.list_models().await?;
+
+        Ok(models
+            .into_iter()
+            .map(|(model_id, display_name)| Model {
+                id: model_id,
+                name: display_name,
+                size: "hosted".to_string(),
+                modified: "unknown".to_string(),
+                provider: ModelProvider::Anthropic,
+                capabilities: vec!["text-generation".to_string(), "instruction-following".to_string()],
+                endpoint: None,
+                details: ModelDetails::default(),
+            })
+            .collect())
+    }
+}
+
+/// Human-readable size for the model list, e.g. "4.7 GB"
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_ollama_show_reads_model_details() {
+        let show = serde_json::json!({
+            "template": "{{ .Prompt }}",
+            "details": {"family": "llama", "quantization_level": "Q4_K_M"},
+            "model_info": {
+                "general.architecture": "llama",
+                "general.parameter_count": 8030261248u64,
+                "llama.context_length": 131072
+            },
+            "capabilities": ["completion", "tools"]
+        });
+        let mut details = ModelDetails::default();
+
+        let capabilities = ModelManager::apply_ollama_show(&show, &mut details);
+        assert_eq!(capabilities, ["text-generation", "tools"]);
+        assert_eq!(details.context_length, Some(131072));
+        assert_eq!(details.parameter_count, Some(8030261248));
+        assert_eq!(details.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(details.template.as_deref(), Some("{{ .Prompt }}"));
+
+        assert_eq!(ModelManager::apply_ollama_show(&serde_json::json!({}), &mut ModelDetails::default()), ["text-generation"]);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(4_661_224_676), "4.7 GB");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,323 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::llm_provider::{
+    CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, LlmProvider, OllamaProvider,
+};
+use crate::retry::{classify, ErrorClass};
+use crate::types::ModelProvider;
+
+/// Consecutive failures after which a host is taken out of rotation
+const MAX_CONSECUTIVE_FAILURES: u32 = 2;
+/// How long an unhealthy host sits out before it is tried again
+const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(60);
+/// Weight given to the newest throughput sample
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Default)]
+struct HostStats {
+    /// Smoothed output characters per second
+    throughput: Option<f64>,
+    in_flight: usize,
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+impl HostStats {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until.is_none_or(|until| now >= until)
+    }
+
+    fn record_success(&mut self, output_chars: usize, elapsed: Duration) {
+        let sample = output_chars as f64 / elapsed.as_secs_f64().max(0.001);
+        self.throughput = Some(match self.throughput {
+            Some(current) => current * (1.0 - THROUGHPUT_SMOOTHING) + sample * THROUGHPUT_SMOOTHING,
+            None => sample,
+        });
+        self.consecutive_failures = 0;
+        self.unhealthy_until = None;
+    }
+
+    fn record_failure(&mut self, now: Instant) -> bool {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+            self.unhealthy_until = Some(now + UNHEALTHY_COOLDOWN);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Counts a request against a host until it finishes or is cancelled
+struct InFlight<'a>(&'a Mutex<HostStats>);
+
+impl<'a> InFlight<'a> {
+    fn start(stats: &'a Mutex<HostStats>) -> Self {
+        stats.lock().unwrap().in_flight += 1;
+        Self(stats)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().in_flight -= 1;
+    }
+}
+
+struct PoolHost {
+    provider: OllamaProvider,
+    stats: Mutex<HostStats>,
+}
+
+/// Spreads requests over several Ollama servers that all serve the same model
+///
+/// Each request goes to the healthy host with the best measured throughput per
+/// in-flight request. Hosts that are unreachable, time out or answer with a
+/// server error are dropped for a cooldown period and the request moves on to
+/// the next host; errors caused by the request itself are returned as they are.
+pub struct OllamaPool {
+    hosts: Vec<PoolHost>,
+}
+
+impl OllamaPool {
+    pub fn new(client: reqwest::Client, base_urls: &[String]) -> Self {
+        Self {
+            hosts: base_urls
+                .iter()
+                .map(|base_url| PoolHost {
+                    provider: OllamaProvider::new(client.clone(), base_url.as_str()),
+                    stats: Mutex::new(HostStats::default()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Build a pool from the hosts that have `model` pulled, skipping unreachable ones
+    pub async fn connect(client: reqwest::Client, base_urls: &[String], model: &str) -> Result<Self> {
+        let checks = base_urls.iter().map(|base_url| {
+            let provider = OllamaProvider::new(client.clone(), base_url.as_str());
+            async move { (base_url.clone(), provider.list_models().await) }
+        });
+
+        let mut hosting = Vec::new();
+        for (base_url, result) in futures::future::join_all(checks).await {
+            match result {
+                Ok(models) if models.iter().any(|name| same_model(name, model)) => hosting.push(base_url),
+                Ok(_) => tracing::warn!("Ollama host {} does not have model {}, skipping", base_url, model),
+                Err(e) => tracing::warn!("Ollama host {} is unreachable, skipping: {}", base_url, e),
+            }
+        }
+
+        if hosting.is_empty() {
+            return Err(anyhow::anyhow!("No configured Ollama host serves model {}", model));
+        }
+
+        tracing::info!("Ollama pool for {}: {:?}", model, hosting);
+        Ok(Self::new(client, &hosting))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hosts.len()
+    }
+
+    pub fn base_urls(&self) -> impl Iterator<Item = &str> {
+        self.hosts.iter().map(|host| host.provider.base_url())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Healthy hosts ordered best first
+    fn ranked_hosts(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let stats: Vec<_> = self.hosts.iter().map(|host| host.stats.lock().unwrap()).collect();
+
+        // Unmeasured hosts get the average measured throughput so they still receive traffic
+        let measured: Vec<f64> = stats.iter().filter_map(|s| s.throughput).collect();
+        let default_throughput = if measured.is_empty() {
+            1.0
+        } else {
+            measured.iter().sum::<f64>() / measured.len() as f64
+        };
+
+        let mut ranked: Vec<(usize, f64)> = stats
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.is_healthy(now))
+            .map(|(index, s)| (index, s.throughput.unwrap_or(default_throughput) / (s.in_flight + 1) as f64))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.into_iter().map(|(index, _)| index).collect()
+    }
+
+    /// Run a request against the ranked hosts until one succeeds, or `can_fail_over` says it may not move on
+    async fn dispatch<'a, T, F, Fut>(
+        &'a self,
+        output_chars: impl Fn(&T) -> usize,
+        can_fail_over: impl Fn() -> bool,
+        call: F,
+    ) -> Result<T>
+    where
+        F: Fn(&'a OllamaProvider) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let ranked = self.ranked_hosts();
+        if ranked.is_empty() {
+            return Err(anyhow::anyhow!("No healthy Ollama hosts available"));
+        }
+
+        let mut last_error = None;
+        for index in ranked {
+            let host = &self.hosts[index];
+            let start = Instant::now();
+            let result = {
+                let _in_flight = InFlight::start(&host.stats);
+                call(&host.provider).await
+            };
+
+            let mut stats = host.stats.lock().unwrap();
+            match result {
+                Ok(value) => {
+                    stats.record_success(output_chars(&value), start.elapsed());
+                    return Ok(value);
+                }
+                Err(e) if !is_host_failure(&e) => return Err(e),
+                Err(e) => {
+                    if stats.record_failure(Instant::now()) {
+                        tracing::warn!("Ollama host {} marked unhealthy: {}", host.provider.base_url(), e);
+                    }
+                    if !can_fail_over() {
+                        return Err(e);
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("All Ollama hosts failed")))
+    }
+}
+
+/// Whether an error says something about the host rather than the request, which every host would reject
+fn is_host_failure(error: &anyhow::Error) -> bool {
+    matches!(classify(error), ErrorClass::Network | ErrorClass::Timeout | ErrorClass::ServerError)
+}
+
+/// Ollama treats an untagged model name as `:latest`
+fn same_model(name: &str, model: &str) -> bool {
+    let with_tag = |value: &str| {
+        if value.contains(':') {
+            value.to_string()
+        } else {
+            format!("{}:latest", value)
+        }
+    };
+    with_tag(name) == with_tag(model)
+}
+
+#[async_trait]
+impl LlmProvider for OllamaPool {
+    fn kind(&self) -> ModelProvider {
+        ModelProvider::Ollama
+    }
+
+    /// Every host of the pool, since any of them may answer a request
+    fn endpoint_url(&self) -> String {
+        self.base_urls().collect::<Vec<_>>().join(",")
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        self.dispatch(|response: &CompletionResponse| response.text.len(), || true, |host| host.complete(request))
+            .await
+    }
+
+    async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        self.dispatch(|response: &CompletionResponse| response.text.len(), || true, |host| host.chat(request))
+            .await
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<CompletionResponse> {
+        // Deltas already handed out belong to the failed host's response; another host would start over
+        let on_delta = Mutex::new(on_delta);
+        let delivered = AtomicBool::new(false);
+        self.dispatch(
+            |response: &CompletionResponse| response.text.len(),
+            || !delivered.load(Ordering::Relaxed),
+            |host| {
+                let (on_delta, delivered) = (&on_delta, &delivered);
+                async move {
+                    host.complete_stream(request, &mut |delta| {
+                        delivered.store(true, Ordering::Relaxed);
+                        (on_delta.lock().unwrap())(delta)
+                    })
+                    .await
+                }
+            },
+        )
+        .await
+    }
+
+    async fn embed(&self, request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+        self.dispatch(|response: &EmbeddingResponse| response.embedding.len(), || true, |host| host.embed(request))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_provider::ApiError;
+    use crate::rate_limiter::RateLimitedError;
+
+    fn pool(hosts: usize) -> OllamaPool {
+        let base_urls: Vec<String> = (0..hosts).map(|i| format!("http://127.0.0.1:{}", 11434 + i)).collect();
+        OllamaPool::new(reqwest::Client::new(), &base_urls)
+    }
+
+    #[test]
+    fn test_ranking_prefers_faster_and_less_busy_hosts() {
+        let pool = pool(3);
+        pool.hosts[0].stats.lock().unwrap().record_success(1000, Duration::from_secs(10));
+        pool.hosts[1].stats.lock().unwrap().record_success(1000, Duration::from_secs(1));
+        pool.hosts[2].stats.lock().unwrap().record_success(1000, Duration::from_secs(2));
+        assert_eq!(pool.ranked_hosts(), vec![1, 2, 0]);
+
+        pool.hosts[1].stats.lock().unwrap().in_flight = 3;
+        assert_eq!(pool.ranked_hosts(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_failing_hosts_are_dropped() {
+        let pool = pool(2);
+        let now = Instant::now();
+        assert!(!pool.hosts[0].stats.lock().unwrap().record_failure(now));
+        assert!(pool.hosts[0].stats.lock().unwrap().record_failure(now));
+        assert_eq!(pool.ranked_hosts(), vec![1]);
+    }
+
+    #[test]
+    fn test_only_host_errors_count_against_host_health() {
+        let api_error = |status| anyhow::Error::new(ApiError { status, message: format!("Ollama API error: {}", status) });
+        assert!(is_host_failure(&api_error(503)));
+        assert!(!is_host_failure(&api_error(400)));
+        assert!(!is_host_failure(&api_error(404)));
+        assert!(!is_host_failure(&RateLimitedError { retry_after: None, message: "busy".to_string() }.into()));
+    }
+
+    #[test]
+    fn test_untagged_model_matches_latest() {
+        assert!(same_model("llama3:latest", "llama3"));
+        assert!(same_model("llama3.2:3b", "llama3.2:3b"));
+        assert!(!same_model("llama3.2:1b", "llama3.2:3b"));
+    }
+}
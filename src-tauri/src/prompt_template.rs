@@ -24,7 +24,7 @@ pub struct CoTExample {
     pub explanation: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PromptContext {
     pub previous_batches_summary: String,
     pub dataset_statistics: DatasetStatistics,
@@ -33,7 +33,7 @@ pub struct PromptContext {
     pub domain_drift_indicators: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DatasetStatistics {
     pub total_entries: usize,
     pub average_quality_score: f32,
@@ -54,6 +54,42 @@ pub struct GenerationPrompt {
     pub negative_sampling_hint: Option<String>,
 }
 
+impl GenerationPrompt {
+    /// The user message sent to the model: the rendered template followed by the guidance sections
+    pub fn user_message(&self) -> String {
+        let sections = [
+            Some(self.user_prompt.as_str()),
+            Some(self.context_instructions.as_str()),
+            Some(self.quality_guidelines.as_str()),
+            Some(self.diversity_instructions.as_str()),
+            self.negative_sampling_hint.as_deref(),
+        ];
+        sections
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .filter(|section| !section.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// One entry of the format as a JSON sample, matching the structured output schema
+pub fn entry_example(format: &DatasetFormat) -> &'static str {
+    match format {
+        DatasetFormat::Alpaca => r#"{"instruction": "...", "input": "...", "output": "..."}"#,
+        DatasetFormat::Conversation => r#"{"messages": [{"role": "user", "content": "..."}, {"role": "assistant", "content": "..."}]}"#,
+        DatasetFormat::ChainOfThought => r#"{"question": "...", "answer": "Step 1: ... Step 2: ... Final Answer: ..."}"#,
+        DatasetFormat::PreferenceRanking => r#"{"prompt": "...", "chosen": "...", "rejected": "..."}"#,
+        DatasetFormat::FunctionCall => r#"{"messages": [{"role": "user", "content": "..."}], "function": {"name": "...", "arguments": "{\"param\": \"value\"}"}}"#,
+        DatasetFormat::MultiRoundDialogue => r#"{"instruction": "...", "conversation": [{"role": "user", "content": "..."}, {"role": "assistant", "content": "..."}]}"#,
+        DatasetFormat::CodeTask => r#"{"prompt": "...", "code": "...", "output": "..."}"#,
+        DatasetFormat::Reflection => r#"{"instruction": "...", "output": "...", "reflection": "...", "corrected": "..."}"#,
+        DatasetFormat::RetrievalEmbedding => r#"{"query": "...", "positive_passage": "...", "negative_passages": ["...", "..."]}"#,
+        DatasetFormat::Reranking => r#"{"query": "...", "documents": ["...", "...", "..."], "relevance_scores": [0.9, 0.4, 0.1]}"#,
+    }
+}
+
 pub struct PromptTemplateEngine {
     templates: HashMap<String, PromptTemplate>,
     default_template: PromptTemplate,
@@ -326,7 +362,8 @@ impl PromptTemplateEngine {
         context: &PromptContext,
     ) -> Result<String> {
         let mut system_prompt = format!(
-            "You are an expert AI trainer specializing in creating high-quality training datasets for the {:?} format.\n\n",
+            "You are an expert AI trainer specializing in creating high-quality training datasets for the {:?} format. \
+            Always respond with valid JSON containing only the requested training examples.\n\n",
             format
         );
 
@@ -386,8 +423,13 @@ impl PromptTemplateEngine {
         // Replace placeholders
         prompt = prompt.replace("{use_case}", use_case);
         prompt = prompt.replace("{batch_size}", &batch_size.to_string());
+        let domain_context = match domain_context.trim() {
+            "" => "General",
+            domain_context => domain_context,
+        };
         prompt = prompt.replace("{domain_context}", domain_context);
         prompt = prompt.replace("{format}", &format!("{:?}", format));
+        prompt = prompt.replace("{entry_example}", entry_example(format));
 
         // Add few-shot examples if available
         if let Some(examples) = template.few_shot_examples.get(format) {
@@ -598,6 +640,7 @@ impl PromptTemplateEngine {
                     "input": "",
                     "output": "Machine learning is a type of artificial intelligence where computers learn to make predictions or decisions by analyzing patterns in data, rather than being explicitly programmed for every possible scenario. Think of it like teaching a child to recognize animals - instead of describing every feature of every animal, you show them many examples, and they learn to identify patterns that help them recognize new animals they haven't seen before."
                 }),
+                source_model: None,
             }
         ]
     }
@@ -609,6 +652,7 @@ impl PromptTemplateEngine {
                     {"role": "user", "content": "I'm having trouble sleeping lately. Any advice?"},
                     {"role": "assistant", "content": "I'm sorry to hear you're having sleep difficulties. Here are some evidence-based tips that might help: establish a consistent bedtime routine, avoid screens 1-2 hours before bed, keep your bedroom cool and dark, and try relaxation techniques like deep breathing. If problems persist, consider consulting a healthcare provider."}
                 ]),
+                source_model: None,
             }
         ]
     }
@@ -620,6 +664,7 @@ impl PromptTemplateEngine {
                     "question": "A store offers a 20% discount on all items. If an item originally costs $50, what is the final price after discount?",
                     "answer": "Step 1: Calculate the discount amount: 20% of $50 = 0.20 × $50 = $10\nStep 2: Subtract the discount from the original price: $50 - $10 = $40\nFinal Answer: The final price after the 20% discount is $40."
                 }),
+                source_model: None,
             }
         ]
     }
@@ -632,6 +677,7 @@ impl PromptTemplateEngine {
                     "chosen": "Recycling is crucial for environmental sustainability because it reduces waste sent to landfills, conserves natural resources by reusing materials, decreases pollution from manufacturing new products, and helps combat climate change by reducing greenhouse gas emissions. For example, recycling one ton of paper saves 17 trees and 7,000 gallons of water.",
                     "rejected": "Recycling is good for the environment. It helps reduce waste and saves resources. People should recycle more."
                 }),
+                source_model: None,
             }
         ]
     }
@@ -641,9 +687,13 @@ impl PromptTemplateEngine {
             DatasetEntry {
                 data: serde_json::json!({
                     "query": "How to bake chocolate chip cookies",
-                    "positive_document": "To bake chocolate chip cookies, preheat oven to 375°F. Mix 2¼ cups flour, 1 tsp salt, and 1 tsp baking soda. In another bowl, cream 1 cup butter with ¾ cup each of brown and white sugar. Add 2 eggs and 2 tsp vanilla. Combine wet and dry ingredients, fold in 2 cups chocolate chips. Drop spoonfuls on baking sheet and bake 9-11 minutes until golden brown.",
-                    "negative_document": "Chocolate chip cookies are a popular dessert enjoyed by many people around the world. They were invented in the 1930s and have become a staple in American households. The key to good cookies is using quality ingredients and proper technique."
+                    "documents": [
+                        "To bake chocolate chip cookies, preheat oven to 375°F. Mix 2¼ cups flour, 1 tsp salt, and 1 tsp baking soda. In another bowl, cream 1 cup butter with ¾ cup each of brown and white sugar. Add 2 eggs and 2 tsp vanilla. Combine wet and dry ingredients, fold in 2 cups chocolate chips. Drop spoonfuls on baking sheet and bake 9-11 minutes until golden brown.",
+                        "Chocolate chip cookies are a popular dessert enjoyed by many people around the world. They were invented in the 1930s and have become a staple in American households. The key to good cookies is using quality ingredients and proper technique."
+                    ],
+                    "relevance_scores": [0.95, 0.2]
                 }),
+                source_model: None,
             }
         ]
     }
@@ -654,3 +704,37 @@ impl Default for PromptTemplateEngine {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompts_cover_every_format_with_domain_context() {
+        let engine = PromptTemplateEngine::new();
+        for format in [
+            DatasetFormat::Alpaca,
+            DatasetFormat::Conversation,
+            DatasetFormat::ChainOfThought,
+            DatasetFormat::PreferenceRanking,
+            DatasetFormat::FunctionCall,
+            DatasetFormat::MultiRoundDialogue,
+            DatasetFormat::CodeTask,
+            DatasetFormat::Reflection,
+            DatasetFormat::RetrievalEmbedding,
+            DatasetFormat::Reranking,
+        ] {
+            let prompt = engine
+                .generate_prompt(&format, "Answer tax questions", 5, &PromptContext::default(), "UK self-assessment")
+                .unwrap();
+            let message = prompt.user_message();
+            assert!(message.contains("UK self-assessment"), "{:?} prompt lacks the domain context", format);
+            assert!(!message.contains("{entry_example}") && !message.contains("{domain_context}"));
+            assert!(message.contains(r#"{"entries": ["#), "{:?} prompt does not ask for the entries wrapper", format);
+
+            // The sample entry in the prompt has the fields the format requires
+            let example: serde_json::Value = serde_json::from_str(entry_example(&format)).unwrap();
+            assert!(crate::dataset::matches_format(&example, &format), "{:?} example does not match its format", format);
+        }
+    }
+}
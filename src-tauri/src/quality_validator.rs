@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use crate::types::{DatasetEntry, DatasetFormat};
+use std::sync::Arc;
+use crate::types::{DatasetEntry, DatasetFormat, ModelProvider};
+use crate::llm_provider::{CompletionRequest, LlmProvider, OllamaProvider};
+use crate::response_cache::CachedProvider;
+use crate::json_recovery::recover_value;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QualityScore {
@@ -39,14 +43,20 @@ pub struct EntryMetadata {
 }
 
 pub struct QualityValidator {
-    client: reqwest::Client,
+    provider: Arc<dyn LlmProvider>,
     model_name: String,
 }
 
 impl QualityValidator {
-    pub fn new(model_name: Option<String>) -> Self {
+    /// Create a validator that judges entries on the Ollama server at `ollama_base_url`
+    pub fn new(model_name: Option<String>, ollama_base_url: &str) -> Self {
+        Self::with_provider(model_name, Arc::new(OllamaProvider::new(reqwest::Client::new(), ollama_base_url)))
+    }
+
+    /// Create a validator that judges entries with the given provider backend
+    pub fn with_provider(model_name: Option<String>, provider: Arc<dyn LlmProvider>) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            provider: CachedProvider::shared(provider),
             model_name: model_name.unwrap_or_else(|| "llama3.2:3b".to_string()),
         }
     }
@@ -153,7 +163,7 @@ impl QualityValidator {
         }
 
         let feedback_prompt = self.create_feedback_prompt(quality_scores, use_case, format);
-        let llm_response = self.query_ollama(&feedback_prompt).await?;
+        let llm_response = self.query_model(&feedback_prompt).await?;
         let feedback = self.parse_feedback_response(&llm_response)?;
 
         Ok(feedback)
@@ -218,7 +228,7 @@ impl QualityValidator {
         
         let validation_prompt = self.create_validation_prompt(entry, use_case, format);
         
-        let llm_response = self.query_ollama(&validation_prompt).await?;
+        let llm_response = self.query_model(&validation_prompt).await?;
         let quality_score = self.parse_quality_response(&llm_response)?;
 
         let metadata = EntryMetadata {
@@ -287,42 +297,23 @@ Be strict but fair. Only give high scores (>0.8) to truly excellent examples."#,
         )
     }
 
-    /// Query the local Ollama LLM
-    async fn query_ollama(&self, prompt: &str) -> Result<String> {
-        let request_body = serde_json::json!({
-            "model": self.model_name,
-            "prompt": prompt,
-            "stream": false,
-            "options": {
-                "temperature": 0.1,
-                "top_p": 0.9,
-                "top_k": 40
-            }
-        });
-
-        let response = self.client
-            .post("http://localhost:11434/api/generate")
-            .json(&request_body)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let result: serde_json::Value = response.json().await?;
-            Ok(result["response"].as_str().unwrap_or("").to_string())
-        } else {
-            let error_text = response.text().await.unwrap_or_default();
-            Err(anyhow::anyhow!("Ollama API error: {}", error_text))
-        }
+    /// Query the judge model through its provider
+    async fn query_model(&self, prompt: &str) -> Result<String> {
+        let request = CompletionRequest::new(self.model_name.clone(), prompt)
+            .with_temperature(0.1)
+            .with_top_p(0.9)
+            .with_top_k(40);
+
+        let response = self.provider.complete(&request).await?;
+        Ok(response.text)
     }
 
     /// Parse the LLM's quality assessment response
     fn parse_quality_response(&self, response: &str) -> Result<QualityScore> {
-        // Try to extract JSON from the response
-        let json_start = response.find('{').unwrap_or(0);
-        let json_end = response.rfind('}').map(|i| i + 1).unwrap_or(response.len());
-        let json_text = &response[json_start..json_end];
+        let parsed = recover_value(response)
+            .and_then(|value| serde_json::from_value::<QualityScore>(value).map_err(|e| e.to_string()));
 
-        match serde_json::from_str::<QualityScore>(json_text) {
+        match parsed {
             Ok(score) => Ok(score),
             Err(e) => {
                 tracing::warn!("Failed to parse quality response: {}", e);
@@ -445,6 +436,10 @@ Be specific and actionable. Focus on patterns that would help the generation mod
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationConfig {
     pub model_name: String,
+    #[serde(default)]
+    pub provider: ModelProvider,
+    #[serde(default)]
+    pub endpoint: Option<String>,
     pub min_quality_score: f32,
     pub enable_validation: bool,
     pub batch_size: usize,
@@ -454,6 +449,8 @@ impl Default for ValidationConfig {
     fn default() -> Self {
         Self {
             model_name: "llama3.2:3b".to_string(),
+            provider: ModelProvider::Ollama,
+            endpoint: None,
             min_quality_score: 0.7,
             enable_validation: true,
             batch_size: 10,
@@ -205,6 +205,7 @@ impl QualityVisualizationService {
             let validated_entry = ValidatedEntry {
                 entry: crate::types::DatasetEntry {
                     data: serde_json::json!({}), // Placeholder
+                    source_model: None,
                 },
                 quality_score: result.final_score.clone(),
                 metadata: crate::quality_validator::EntryMetadata {
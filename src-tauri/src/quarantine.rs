@@ -0,0 +1,152 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::llm_provider::CompletionRequest;
+use crate::types::{GenerationTask, ModelProvider};
+
+/// Responses kept before the oldest are dropped
+const MAX_QUARANTINED_RESPONSES: usize = 200;
+
+/// A model response that could not be parsed into entries
+#[derive(Debug, Clone)]
+pub struct UnparseableResponse {
+    pub raw_output: String,
+    pub error: String,
+}
+
+impl fmt::Display for UnparseableResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unparseable model response: {}", self.error)
+    }
+}
+
+impl std::error::Error for UnparseableResponse {}
+
+/// An unparseable response kept out of the dataset, with what is needed to inspect or retry it
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedResponse {
+    pub id: String,
+    /// Run the response belongs to; a retry only adds entries to that run's dataset
+    pub generation_id: String,
+    pub batch_id: usize,
+    pub model_id: String,
+    pub provider: ModelProvider,
+    pub endpoint: Option<String>,
+    /// Entries the request asked for
+    pub expected_entries: usize,
+    pub raw_output: String,
+    pub error: String,
+    pub quarantined_at: DateTime<Utc>,
+    /// Request that produced the output, sent again on retry
+    pub request: CompletionRequest,
+}
+
+impl QuarantinedResponse {
+    pub fn new(
+        task: &GenerationTask,
+        generation_id: &str,
+        expected_entries: usize,
+        request: CompletionRequest,
+        response: UnparseableResponse,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            generation_id: generation_id.to_string(),
+            batch_id: task.batch_id,
+            model_id: task.model_id.clone(),
+            provider: task.provider.clone(),
+            endpoint: task.endpoint.clone(),
+            expected_entries,
+            raw_output: response.raw_output,
+            error: response.error,
+            quarantined_at: Utc::now(),
+            request,
+        }
+    }
+}
+
+/// Unparseable responses awaiting review, shared between the generator and the app
+///
+/// Clones share the same responses. Only the most recent `MAX_QUARANTINED_RESPONSES` are kept.
+#[derive(Debug, Clone, Default)]
+pub struct QuarantineStore {
+    responses: Arc<Mutex<Vec<QuarantinedResponse>>>,
+}
+
+impl QuarantineStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, response: QuarantinedResponse) {
+        let mut responses = self.responses.lock().unwrap();
+        responses.push(response);
+        let excess = responses.len().saturating_sub(MAX_QUARANTINED_RESPONSES);
+        responses.drain(..excess);
+    }
+
+    /// Quarantined responses, oldest first
+    pub fn list(&self) -> Vec<QuarantinedResponse> {
+        self.responses.lock().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<QuarantinedResponse> {
+        self.responses.lock().unwrap().iter().find(|response| response.id == id).cloned()
+    }
+
+    /// Replace the output and error of a response whose retry failed to parse again
+    pub fn update(&self, id: &str, response: UnparseableResponse) {
+        let mut responses = self.responses.lock().unwrap();
+        if let Some(quarantined) = responses.iter_mut().find(|quarantined| quarantined.id == id) {
+            quarantined.raw_output = response.raw_output;
+            quarantined.error = response.error;
+            quarantined.quarantined_at = Utc::now();
+        }
+    }
+
+    pub fn remove(&self, id: &str) -> Option<QuarantinedResponse> {
+        let mut responses = self.responses.lock().unwrap();
+        let index = responses.iter().position(|response| response.id == id)?;
+        Some(responses.remove(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset_concurrent::parse_generated_entries;
+
+    #[test]
+    fn test_quarantines_unparseable_output_until_resolved() {
+        let task = GenerationTask::for_test(3, "llama3");
+        let unparseable = parse_generated_entries("Sure! Here are your examples: [{\"instruction\": ").unwrap_err();
+        assert!(unparseable.raw_output.starts_with("Sure!"));
+
+        let store = QuarantineStore::new();
+        let quarantined = QuarantinedResponse::new(&task, "run", 5, CompletionRequest::new("llama3", "prompt"), unparseable.clone());
+        let id = quarantined.id.clone();
+        store.clone().add(quarantined);
+        assert_eq!(store.get(&id).unwrap().batch_id, 3);
+
+        store.update(&id, UnparseableResponse { raw_output: "[]".to_string(), error: "empty".to_string() });
+        assert_eq!(store.list()[0].raw_output, "[]");
+        assert!(store.remove(&id).is_some());
+        assert!(store.is_empty() && store.remove(&id).is_none());
+
+        for _ in 0..MAX_QUARANTINED_RESPONSES + 5 {
+            store.add(QuarantinedResponse::new(&task, "run", 5, CompletionRequest::new("llama3", "prompt"), unparseable.clone()));
+        }
+        assert_eq!(store.len(), MAX_QUARANTINED_RESPONSES);
+    }
+}
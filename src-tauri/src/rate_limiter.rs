@@ -0,0 +1,288 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::llm_provider::{CompletionRequest, TokenUsage};
+
+/// Backoff after a 429 that came without a Retry-After header; doubles on each consecutive 429
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Completion tokens assumed for requests that do not set max_tokens
+const DEFAULT_COMPLETION_ESTIMATE: u64 = 1000;
+
+/// Request and token allowances per minute; no token limit when `tokens_per_minute` is None
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimits {
+    pub requests_per_minute: u32,
+    pub tokens_per_minute: Option<u64>,
+}
+
+impl RateLimits {
+    pub fn new(requests_per_minute: u32, tokens_per_minute: Option<u64>) -> Self {
+        Self { requests_per_minute, tokens_per_minute }
+    }
+}
+
+/// Rate-limit state reported by a provider in its response headers
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitHeaders {
+    pub remaining_requests: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+    pub reset_requests: Option<Duration>,
+    pub reset_tokens: Option<Duration>,
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitHeaders {
+    /// Read OpenAI `x-ratelimit-*`, Anthropic `anthropic-ratelimit-*` and `Retry-After` headers
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+        let first = |names: [&str; 2]| names.into_iter().find_map(header);
+        let count = |names| first(names).and_then(|value| value.parse().ok());
+        let reset = |names| first(names).and_then(parse_reset);
+
+        let parsed = Self {
+            remaining_requests: count(["x-ratelimit-remaining-requests", "anthropic-ratelimit-requests-remaining"]),
+            remaining_tokens: count(["x-ratelimit-remaining-tokens", "anthropic-ratelimit-tokens-remaining"]),
+            reset_requests: reset(["x-ratelimit-reset-requests", "anthropic-ratelimit-requests-reset"]),
+            reset_tokens: reset(["x-ratelimit-reset-tokens", "anthropic-ratelimit-tokens-reset"]),
+            retry_after: header("retry-after-ms")
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis)
+                .or_else(|| header("retry-after").and_then(parse_reset)),
+        };
+        (parsed != Self::default()).then_some(parsed)
+    }
+}
+
+/// Parse a reset time: seconds ("20"), an OpenAI duration ("6m0s", "250ms") or an RFC 3339 timestamp
+fn parse_reset(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds >= 0.0).then(|| Duration::from_secs_f64(seconds));
+    }
+    if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(value) {
+        let millis = (timestamp.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_milliseconds();
+        return Some(Duration::from_millis(millis.max(0) as u64));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let amount: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        total += Duration::from_secs_f64(amount * unit);
+        rest = &rest[unit_len..];
+    }
+    Some(total)
+}
+
+/// Returned by providers when the API answers 429 Too Many Requests
+#[derive(Debug)]
+pub struct RateLimitedError {
+    pub retry_after: Option<Duration>,
+    pub message: String,
+}
+
+impl fmt::Display for RateLimitedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RateLimitedError {}
+
+/// Rough token cost of a request: about four characters per prompt token plus the completion allowance
+pub fn estimate_tokens(request: &CompletionRequest) -> u64 {
+    let prompt_chars = request.prompt().len() + request.system.as_ref().map_or(0, String::len);
+    prompt_chars as u64 / 4 + request.max_tokens.map_or(DEFAULT_COMPLETION_ESTIMATE, u64::from)
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+}
+
+impl Bucket {
+    fn per_minute(capacity: f64) -> Self {
+        Self { capacity, available: capacity, per_second: capacity / 60.0 }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available = (self.available + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+    }
+
+    /// Time until `amount` is available; requests above capacity only wait for a full bucket
+    fn wait_for(&self, amount: f64) -> Duration {
+        let shortfall = amount.min(self.capacity) - self.available;
+        if shortfall <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(shortfall / self.per_second)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    requests: Bucket,
+    tokens: Option<Bucket>,
+    refilled_at: Instant,
+    paused_until: Option<Instant>,
+    consecutive_rate_limits: u32,
+}
+
+impl LimiterState {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at);
+        self.refilled_at = now;
+        self.requests.refill(elapsed);
+        if let Some(tokens) = &mut self.tokens {
+            tokens.refill(elapsed);
+        }
+    }
+
+    fn pause_until(&mut self, until: Instant) {
+        self.paused_until = Some(self.paused_until.map_or(until, |paused| paused.max(until)));
+    }
+}
+
+/// Token-bucket limiter on requests and tokens per minute, shared by every batch of a run
+///
+/// Clones share state, so a 429 seen by one batch pauses all of them.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    state: Arc<Mutex<LimiterState>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(LimiterState {
+                requests: Bucket::per_minute(limits.requests_per_minute.max(1) as f64),
+                tokens: limits.tokens_per_minute.map(|tokens| Bucket::per_minute(tokens.max(1) as f64)),
+                refilled_at: Instant::now(),
+                paused_until: None,
+                consecutive_rate_limits: 0,
+            })),
+        }
+    }
+
+    /// Wait for a request slot and `estimated_tokens` of token allowance, then take them
+    pub async fn acquire(&self, estimated_tokens: u64) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                state.refill(now);
+
+                let paused = state.paused_until.map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+                let requests = state.requests.wait_for(1.0);
+                let tokens = state.tokens.as_ref().map_or(Duration::ZERO, |tokens| tokens.wait_for(estimated_tokens as f64));
+                let wait = paused.max(requests).max(tokens);
+
+                if wait.is_zero() {
+                    state.requests.available -= 1.0;
+                    if let Some(tokens) = &mut state.tokens {
+                        tokens.available -= (estimated_tokens as f64).min(tokens.capacity);
+                    }
+                    return;
+                }
+                wait
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Settle a completed request: correct the token estimate and adopt the server's view of the limits
+    pub fn record_response(&self, estimated_tokens: u64, usage: Option<&TokenUsage>, headers: Option<&RateLimitHeaders>) {
+        let mut state = self.state.lock().unwrap();
+        state.refill(Instant::now());
+        state.consecutive_rate_limits = 0;
+
+        if let (Some(tokens), Some(usage)) = (&mut state.tokens, usage) {
+            let difference = estimated_tokens as f64 - usage.total_tokens() as f64;
+            tokens.available = (tokens.available + difference).min(tokens.capacity);
+        }
+
+        let Some(headers) = headers else {
+            return;
+        };
+        if let Some(remaining) = headers.remaining_requests {
+            state.requests.available = state.requests.available.min(remaining as f64);
+        }
+        if let (Some(tokens), Some(remaining)) = (&mut state.tokens, headers.remaining_tokens) {
+            tokens.available = tokens.available.min(remaining as f64);
+        }
+        let now = Instant::now();
+        if let (Some(0), Some(reset)) = (headers.remaining_requests, headers.reset_requests) {
+            state.pause_until(now + reset);
+        }
+        if let (Some(0), Some(reset)) = (headers.remaining_tokens, headers.reset_tokens) {
+            state.pause_until(now + reset);
+        }
+    }
+
+    /// Pause every request after a 429, for the server's Retry-After or an exponential default
+    pub fn back_off(&self, retry_after: Option<Duration>) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let default = DEFAULT_BACKOFF.saturating_mul(2u32.saturating_pow(state.consecutive_rate_limits)).min(MAX_BACKOFF);
+        let delay = retry_after.unwrap_or(default);
+        state.consecutive_rate_limits += 1;
+        state.pause_until(Instant::now() + delay);
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_parses_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-remaining-tokens", HeaderValue::from_static("149984"));
+        headers.insert("x-ratelimit-reset-requests", HeaderValue::from_static("1m30.5s"));
+        headers.insert("x-ratelimit-reset-tokens", HeaderValue::from_static("6ms"));
+        headers.insert("retry-after", HeaderValue::from_static("2"));
+
+        let parsed = RateLimitHeaders::from_headers(&headers).unwrap();
+        assert_eq!(parsed.remaining_requests, Some(0));
+        assert_eq!(parsed.remaining_tokens, Some(149_984));
+        assert_eq!(parsed.reset_requests, Some(Duration::from_millis(90_500)));
+        assert_eq!(parsed.reset_tokens, Some(Duration::from_millis(6)));
+        assert_eq!(parsed.retry_after, Some(Duration::from_secs(2)));
+        assert!(RateLimitHeaders::from_headers(&HeaderMap::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_tokens_and_pauses_after_rate_limit() {
+        let limiter = RateLimiter::new(RateLimits::new(6000, Some(60_000)));
+        let started = Instant::now();
+
+        limiter.acquire(60_000).await;
+        limiter.acquire(100).await;
+        assert!(started.elapsed() >= Duration::from_millis(90));
+
+        let delay = limiter.clone().back_off(None);
+        assert_eq!(delay, DEFAULT_BACKOFF);
+        assert_eq!(limiter.back_off(None), DEFAULT_BACKOFF * 2);
+        limiter.record_response(0, None, None);
+        assert_eq!(limiter.back_off(Some(Duration::from_secs(5))), Duration::from_secs(5));
+    }
+}
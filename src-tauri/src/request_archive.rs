@@ -0,0 +1,321 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::llm_provider::{CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, LlmProvider, TokenUsage};
+use crate::rate_limiter::RateLimitHeaders;
+use crate::retry::{classify, ErrorClass};
+use crate::settings::{data_dir, ProviderSettings};
+use crate::types::ModelProvider;
+
+const ARCHIVE_DIR: &str = "request_archive";
+const REDACTED: &str = "[REDACTED]";
+
+/// Whether provider calls are archived, how many runs are kept and whether secrets are scrubbed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    /// Off until turned on in the settings, since archives hold full prompts and responses
+    pub enabled: bool,
+    /// Archives of older runs are deleted once there are more than this many
+    pub max_runs: usize,
+    /// Replace API keys with a placeholder and drop response headers
+    pub redact: bool,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self { enabled: false, max_runs: 20, redact: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallStatus {
+    Success,
+    Error,
+}
+
+/// One provider call of a generation run, as sent and as answered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedCall {
+    pub generation_id: String,
+    pub task_id: String,
+    pub batch_id: usize,
+    pub provider: ModelProvider,
+    pub model: String,
+    pub started_at: DateTime<Utc>,
+    pub latency_ms: u64,
+    pub status: CallStatus,
+    pub error: Option<String>,
+    pub error_class: Option<ErrorClass>,
+    /// The request as sent: prompt, system prompt and sampling parameters
+    pub request: serde_json::Value,
+    pub response_text: Option<String>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+    pub rate_limit_headers: Option<RateLimitHeaders>,
+}
+
+/// Append-only log of the provider calls of one run, one JSON line per call
+pub struct RequestArchive {
+    path: PathBuf,
+    generation_id: String,
+    /// Strings scrubbed from every record; empty when redaction is off
+    secrets: Vec<String>,
+    redact: bool,
+    file: Mutex<()>,
+}
+
+impl RequestArchive {
+    pub fn new(dir: &Path, generation_id: &str, redact: bool, secrets: Vec<String>) -> Self {
+        Self {
+            path: archive_path(dir, generation_id),
+            generation_id: generation_id.to_string(),
+            secrets: if redact { secrets } else { Vec::new() },
+            redact,
+            file: Mutex::new(()),
+        }
+    }
+
+    /// Archive for a new run in the data directory, after pruning runs beyond the retention limit
+    pub fn for_run(generation_id: &str, settings: &ProviderSettings) -> Arc<RequestArchive> {
+        let dir = data_dir().join(ARCHIVE_DIR);
+        if let Err(e) = prune(&dir, settings.request_archive.max_runs.saturating_sub(1)) {
+            tracing::warn!("Failed to prune the request archive: {}", e);
+        }
+        Arc::new(Self::new(&dir, generation_id, settings.request_archive.redact, known_secrets(settings)))
+    }
+
+    /// Append a call; failures are logged since the archive is only a debugging aid
+    pub async fn record(&self, call: ArchivedCall) {
+        let call = if self.redact { self.redacted(call) } else { call };
+        let result = async {
+            let mut line = serde_json::to_vec(&call)?;
+            line.push(b'\n');
+            let _guard = self.file.lock().await;
+            if let Some(dir) = self.path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&self.path).await?;
+            file.write_all(&line).await?;
+            // tokio finishes writes in the background; flush so the line is on disk when this returns
+            file.flush().await?;
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to archive call for batch {}: {}", call.batch_id, e);
+        }
+    }
+
+    fn redacted(&self, mut call: ArchivedCall) -> ArchivedCall {
+        let scrub = |text: &mut String| {
+            for secret in &self.secrets {
+                *text = text.replace(secret.as_str(), REDACTED);
+            }
+        };
+        if let Some(error) = &mut call.error {
+            scrub(error);
+        }
+        if let Some(text) = &mut call.response_text {
+            scrub(text);
+        }
+        let mut request = call.request.to_string();
+        scrub(&mut request);
+        call.request = serde_json::from_str(&request).unwrap_or(serde_json::Value::Null);
+        call.rate_limit_headers = None;
+        call
+    }
+}
+
+fn archive_path(dir: &Path, generation_id: &str) -> PathBuf {
+    // Generation ids are UUIDs; anything else is kept from escaping the archive directory
+    let file_name: String = generation_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    dir.join(format!("{}.jsonl", file_name))
+}
+
+/// Calls archived for a run, in the order they finished
+pub fn load(generation_id: &str) -> Result<Vec<ArchivedCall>> {
+    load_from(&data_dir().join(ARCHIVE_DIR), generation_id)
+}
+
+fn load_from(dir: &Path, generation_id: &str) -> Result<Vec<ArchivedCall>> {
+    let path = archive_path(dir, generation_id);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+/// Delete the oldest run archives so that at most `keep` remain
+fn prune(dir: &Path, keep: usize) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    let mut archives = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_some_and(|extension| extension == "jsonl") {
+            archives.push((entry.metadata()?.modified()?, path));
+        }
+    }
+    archives.sort();
+    let excess = archives.len().saturating_sub(keep);
+    for (_, path) in archives.into_iter().take(excess) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// API keys that may appear in requests or error messages
+fn known_secrets(settings: &ProviderSettings) -> Vec<String> {
+    let configured = settings.openai_compatible_endpoints.iter().filter_map(|endpoint| endpoint.api_key.clone());
+    let hosted = [ModelProvider::OpenAI, ModelProvider::Anthropic]
+        .iter()
+        .filter_map(|provider| settings.api_key(provider));
+    configured
+        .chain(hosted)
+        .chain(std::env::var("OPENAI_COMPATIBLE_API_KEY").ok())
+        .filter(|secret| !secret.trim().is_empty())
+        .collect()
+}
+
+/// Provider wrapper that archives every completion made for one generation task
+pub struct ArchivingProvider {
+    inner: Arc<dyn LlmProvider>,
+    archive: Arc<RequestArchive>,
+    task_id: String,
+    batch_id: usize,
+}
+
+impl ArchivingProvider {
+    pub fn wrap(inner: Arc<dyn LlmProvider>, archive: Arc<RequestArchive>, task_id: &str, batch_id: usize) -> Arc<dyn LlmProvider> {
+        Arc::new(Self { inner, archive, task_id: task_id.to_string(), batch_id })
+    }
+
+    async fn record(&self, request: &CompletionRequest, started_at: DateTime<Utc>, started: Instant, result: &Result<CompletionResponse>) {
+        let (response, error) = match result {
+            Ok(response) => (Some(response), None),
+            Err(e) => (None, Some(e)),
+        };
+        self.archive.record(ArchivedCall {
+            generation_id: self.archive.generation_id.clone(),
+            task_id: self.task_id.clone(),
+            batch_id: self.batch_id,
+            provider: self.inner.kind(),
+            model: request.model.clone(),
+            started_at,
+            latency_ms: started.elapsed().as_millis() as u64,
+            status: if error.is_some() { CallStatus::Error } else { CallStatus::Success },
+            error: error.map(|e| format!("{:#}", e)),
+            error_class: error.map(classify),
+            request: serde_json::to_value(request).unwrap_or_default(),
+            response_text: response.map(|response| response.text.clone()),
+            finish_reason: response.and_then(|response| response.finish_reason.clone()),
+            usage: response.and_then(|response| response.usage.clone()),
+            rate_limit_headers: response.and_then(|response| response.rate_limit.clone()),
+        })
+        .await;
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ArchivingProvider {
+    fn kind(&self) -> ModelProvider {
+        self.inner.kind()
+    }
+
+    fn endpoint_url(&self) -> String {
+        self.inner.endpoint_url()
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let (started_at, started) = (Utc::now(), Instant::now());
+        let result = self.inner.complete(request).await;
+        self.record(request, started_at, started, &result).await;
+        result
+    }
+
+    async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let (started_at, started) = (Utc::now(), Instant::now());
+        let result = self.inner.chat(request).await;
+        self.record(request, started_at, started, &result).await;
+        result
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<CompletionResponse> {
+        let (started_at, started) = (Utc::now(), Instant::now());
+        let result = self.inner.complete_stream(request, on_delta).await;
+        self.record(request, started_at, started, &result).await;
+        result
+    }
+
+    async fn embed(&self, request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+        self.inner.embed(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_archives_redacted_calls_and_prunes_old_runs() {
+        let dir = std::env::temp_dir().join(format!("request_archive_test_{}", uuid::Uuid::new_v4()));
+        let archive = RequestArchive::new(&dir, "run-1", true, vec!["sk-secret".to_string()]);
+        let call = ArchivedCall {
+            generation_id: "run-1".to_string(),
+            task_id: "task".to_string(),
+            batch_id: 2,
+            provider: ModelProvider::OpenAI,
+            model: "gpt-4o-mini".to_string(),
+            started_at: Utc::now(),
+            latency_ms: 120,
+            status: CallStatus::Error,
+            error: Some("401: invalid key sk-secret".to_string()),
+            error_class: Some(ErrorClass::Auth),
+            request: serde_json::to_value(CompletionRequest::new("gpt-4o-mini", "uses sk-secret")).unwrap(),
+            response_text: None,
+            finish_reason: None,
+            usage: None,
+            rate_limit_headers: Some(RateLimitHeaders::default()),
+        };
+        archive.record(call.clone()).await;
+        archive.record(ArchivedCall { batch_id: 3, ..call }).await;
+
+        let calls = load_from(&dir, "run-1").unwrap();
+        assert_eq!(calls.iter().map(|call| call.batch_id).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(calls[0].error.as_deref(), Some("401: invalid key [REDACTED]"));
+        assert!(!calls[0].request.to_string().contains("sk-secret"));
+        assert!(calls[0].rate_limit_headers.is_none());
+
+        // Pruning goes by modification time, so make run-1 clearly the older archive
+        let hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        std::fs::File::options().append(true).open(archive_path(&dir, "run-1")).unwrap().set_modified(hour_ago).unwrap();
+        RequestArchive::new(&dir, "run-2", false, Vec::new()).record(calls[0].clone()).await;
+        prune(&dir, 1).unwrap();
+        assert!(load_from(&dir, "run-1").unwrap().is_empty());
+        assert_eq!(load_from(&dir, "run-2").unwrap().len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::llm_provider::{CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, LlmProvider};
+use crate::settings::data_dir;
+use crate::types::ModelProvider;
+
+const CACHE_DIR: &str = "response_cache";
+
+/// Hit counts since startup and the size of the cache on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f32,
+    pub entries: usize,
+    pub size_bytes: u64,
+}
+
+/// Content-addressed store of provider responses, one JSON file per request
+pub struct ResponseCache {
+    dir: PathBuf,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The app-wide cache in the data directory
+    pub fn shared() -> Arc<ResponseCache> {
+        static SHARED: OnceLock<Arc<ResponseCache>> = OnceLock::new();
+        SHARED.get_or_init(|| Arc::new(Self::new(data_dir().join(CACHE_DIR)))).clone()
+    }
+
+    /// SHA-256 of the serialized key material, encoded so it is safe as a file name
+    pub fn key(material: &impl Serialize) -> String {
+        use sha2::{Sha256, Digest};
+        use base64::{Engine as _, engine::general_purpose};
+
+        let content = serde_json::to_string(material).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(content.as_bytes());
+        let hash = hasher.finalize();
+        general_purpose::URL_SAFE_NO_PAD.encode(hash)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let cached = tokio::fs::read_to_string(self.path(key))
+            .await
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+        let counter = if cached.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// Store a response; failures are logged since the cache is only an optimisation
+    pub async fn put<T: Serialize>(&self, key: &str, value: &T) {
+        let result = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            tokio::fs::write(self.path(key), serde_json::to_vec(value)?).await?;
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to write response cache entry {}: {}", key, e);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let (entries, size_bytes) = std::fs::read_dir(&self.dir)
+            .map(|dir| {
+                dir.filter_map(|entry| entry.ok()?.metadata().ok())
+                    .filter(|metadata| metadata.is_file())
+                    .fold((0, 0), |(count, size), metadata| (count + 1, size + metadata.len()))
+            })
+            .unwrap_or((0, 0));
+
+        CacheStats {
+            hits,
+            misses,
+            hit_rate: if hits + misses == 0 { 0.0 } else { hits as f32 / (hits + misses) as f32 },
+            entries,
+            size_bytes,
+        }
+    }
+
+    /// Delete every cached response and reset the hit counters, returning how many were removed
+    pub fn clear(&self) -> Result<usize> {
+        let mut removed = 0;
+        if self.dir.exists() {
+            for entry in std::fs::read_dir(&self.dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|extension| extension == "json") {
+                    std::fs::remove_file(path)?;
+                    removed += 1;
+                }
+            }
+        }
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+        Ok(removed)
+    }
+}
+
+/// Provider wrapper that answers repeated requests from the response cache
+pub struct CachedProvider {
+    inner: Arc<dyn LlmProvider>,
+    cache: Arc<ResponseCache>,
+    cacheable: fn(&CompletionResponse) -> bool,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Arc<dyn LlmProvider>, cache: Arc<ResponseCache>) -> Self {
+        Self { inner, cache, cacheable: |_| true }
+    }
+
+    /// Only store completions that pass this check, so unusable responses are not replayed
+    pub fn caching_only(mut self, cacheable: fn(&CompletionResponse) -> bool) -> Self {
+        self.cacheable = cacheable;
+        self
+    }
+
+    /// Wrap a provider with the app-wide cache
+    pub fn shared(inner: Arc<dyn LlmProvider>) -> Arc<dyn LlmProvider> {
+        Arc::new(Self::new(inner, ResponseCache::shared()))
+    }
+
+    fn completion_key(&self, operation: &str, request: &CompletionRequest) -> String {
+        ResponseCache::key(&serde_json::json!({
+            "operation": operation,
+            "provider": self.inner.kind(),
+            "endpoint": self.inner.endpoint_url(),
+            "request": request,
+        }))
+    }
+
+    /// A stored completion, marked so its usage is not counted again
+    async fn cached(&self, key: &str) -> Option<CompletionResponse> {
+        let response: CompletionResponse = self.cache.get(key).await?;
+        Some(CompletionResponse { cached: true, ..response })
+    }
+
+    async fn store(&self, key: &str, response: &CompletionResponse) {
+        if (self.cacheable)(response) {
+            self.cache.put(key, response).await;
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for CachedProvider {
+    fn kind(&self) -> ModelProvider {
+        self.inner.kind()
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let key = self.completion_key("complete", request);
+        if let Some(response) = self.cached(&key).await {
+            return Ok(response);
+        }
+        let response = self.inner.complete(request).await?;
+        self.store(&key, &response).await;
+        Ok(response)
+    }
+
+    async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let key = self.completion_key("chat", request);
+        if let Some(response) = self.cached(&key).await {
+            return Ok(response);
+        }
+        let response = self.inner.chat(request).await?;
+        self.store(&key, &response).await;
+        Ok(response)
+    }
+
+    /// Shares entries with `complete`; a cached response is delivered as a single delta
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<CompletionResponse> {
+        let key = self.completion_key("complete", request);
+        if let Some(response) = self.cached(&key).await {
+            on_delta(&response.text);
+            return Ok(response);
+        }
+        let response = self.inner.complete_stream(request, on_delta).await?;
+        self.store(&key, &response).await;
+        Ok(response)
+    }
+
+    async fn embed(&self, request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+        let key = ResponseCache::key(&serde_json::json!({
+            "operation": "embed",
+            "provider": self.inner.kind(),
+            "endpoint": self.inner.endpoint_url(),
+            "model": request.model,
+            "input": request.input,
+        }));
+        if let Some(response) = self.cache.get(&key).await {
+            return Ok(response);
+        }
+        let response = self.inner.embed(request).await?;
+        self.cache.put(&key, &response).await;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountingProvider {
+        calls: AtomicUsize,
+        endpoint: &'static str,
+    }
+
+    #[async_trait]
+    impl LlmProvider for CountingProvider {
+        fn kind(&self) -> ModelProvider {
+            ModelProvider::Ollama
+        }
+
+        fn endpoint_url(&self) -> String {
+            self.endpoint.to_string()
+        }
+
+        async fn chat(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(CompletionResponse {
+                text: format!("response {}", call),
+                model: request.model.clone(),
+                finish_reason: None,
+                usage: None,
+                system_fingerprint: None,
+                rate_limit: None,
+                cached: false,
+            })
+        }
+
+        async fn embed(&self, _request: &EmbeddingRequest) -> Result<EmbeddingResponse> {
+            Ok(EmbeddingResponse { embedding: vec![0.5, 0.25] })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_requests_are_served_from_disk() {
+        let dir = std::env::temp_dir().join(format!("response_cache_test_{}", uuid::Uuid::new_v4()));
+        let cache = Arc::new(ResponseCache::new(&dir));
+        let inner = Arc::new(CountingProvider { calls: AtomicUsize::new(0), endpoint: "http://a:11434" });
+        let provider = CachedProvider::new(inner.clone(), cache.clone());
+
+        let request = CompletionRequest::new("llama3.2", "hello").with_temperature(0.7);
+        let first = provider.complete(&request).await.unwrap();
+        let second = provider.complete(&request).await.unwrap();
+        assert_eq!(first.text, second.text);
+        assert!(!first.cached && second.cached);
+
+        let mut streamed = String::new();
+        let third = provider.complete_stream(&request, &mut |delta| streamed.push_str(delta)).await.unwrap();
+        assert_eq!((third.text.as_str(), streamed.as_str()), ("response 0", "response 0"));
+
+        let changed = provider.complete(&request.clone().with_temperature(0.9)).await.unwrap();
+        assert_eq!(changed.text, "response 1");
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 2, 2));
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert_eq!(cache.stats().entries, 0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_cache_separates_endpoints_and_skips_uncacheable_responses() {
+        let dir = std::env::temp_dir().join(format!("response_cache_test_{}", uuid::Uuid::new_v4()));
+        let cache = Arc::new(ResponseCache::new(&dir));
+        let request = CompletionRequest::new("llama3.2", "hello");
+
+        let first_host = Arc::new(CountingProvider { calls: AtomicUsize::new(0), endpoint: "http://a:11434" });
+        CachedProvider::new(first_host, cache.clone()).complete(&request).await.unwrap();
+        let second_host = Arc::new(CountingProvider { calls: AtomicUsize::new(0), endpoint: "http://b:11434" });
+        CachedProvider::new(second_host.clone(), cache.clone()).complete(&request).await.unwrap();
+        assert_eq!(second_host.calls.load(Ordering::SeqCst), 1);
+
+        let inner = Arc::new(CountingProvider { calls: AtomicUsize::new(0), endpoint: "http://c:11434" });
+        let provider = CachedProvider::new(inner.clone(), cache.clone()).caching_only(|response| response.text != "response 0");
+        assert_eq!(provider.complete(&request).await.unwrap().text, "response 0");
+        assert_eq!(provider.complete(&request).await.unwrap().text, "response 1");
+        assert_eq!(provider.complete(&request).await.unwrap().text, "response 1");
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm_provider::ApiError;
+use crate::quarantine::UnparseableResponse;
+use crate::rate_limiter::RateLimitedError;
+
+/// What went wrong with a request, deciding whether retrying it can help
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    Timeout,
+    RateLimited,
+    ServerError,
+    ParseFailure,
+    Network,
+    Auth,
+    ModelNotFound,
+    InvalidRequest,
+    Other,
+}
+
+impl ErrorClass {
+    /// Fatal errors fail the same way on every attempt, so the run is aborted instead of retried
+    pub fn is_fatal(self) -> bool {
+        matches!(self, ErrorClass::Auth | ErrorClass::ModelNotFound | ErrorClass::InvalidRequest)
+    }
+
+    fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => ErrorClass::Auth,
+            404 => ErrorClass::ModelNotFound,
+            408 => ErrorClass::Timeout,
+            429 => ErrorClass::RateLimited,
+            500..=599 => ErrorClass::ServerError,
+            400..=499 => ErrorClass::InvalidRequest,
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::RateLimited => "rate limit",
+            ErrorClass::ServerError => "server",
+            ErrorClass::ParseFailure => "parse",
+            ErrorClass::Network => "network",
+            ErrorClass::Auth => "authentication",
+            ErrorClass::ModelNotFound => "model not found",
+            ErrorClass::InvalidRequest => "invalid request",
+            ErrorClass::Other => "unclassified",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Classify an error by the first recognisable cause in its chain
+pub fn classify(error: &anyhow::Error) -> ErrorClass {
+    for cause in error.chain() {
+        if cause.is::<RateLimitedError>() {
+            return ErrorClass::RateLimited;
+        }
+        if let Some(api_error) = cause.downcast_ref::<ApiError>() {
+            return ErrorClass::from_status(api_error.status);
+        }
+        if let Some(reqwest_error) = cause.downcast_ref::<reqwest::Error>() {
+            if reqwest_error.is_timeout() {
+                return ErrorClass::Timeout;
+            }
+            if let Some(status) = reqwest_error.status() {
+                return ErrorClass::from_status(status.as_u16());
+            }
+            if reqwest_error.is_decode() {
+                return ErrorClass::ParseFailure;
+            }
+            return ErrorClass::Network;
+        }
+        if cause.is::<serde_json::Error>() || cause.is::<UnparseableResponse>() {
+            return ErrorClass::ParseFailure;
+        }
+    }
+    ErrorClass::Other
+}
+
+/// Exponential backoff with jitter for retryable errors
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(base_delay: Duration, max_delay: Duration) -> Self {
+        Self { base_delay, max_delay }
+    }
+
+    /// Delay before retry number `attempt` (from 0): half the doubled delay is fixed, half is random
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt as u32))
+            .min(self.max_delay);
+        let half = ceiling / 2;
+        half + half.mul_f64(jitter())
+    }
+}
+
+/// A random fraction in [0, 1), without pulling in a random number crate
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_errors_and_backs_off_exponentially() {
+        let status = |status| anyhow::Error::new(ApiError { status, message: String::new() });
+        assert_eq!(classify(&status(401)), ErrorClass::Auth);
+        assert_eq!(classify(&status(404).context("Batch 3 failed")), ErrorClass::ModelNotFound);
+        assert_eq!(classify(&status(503)), ErrorClass::ServerError);
+        assert_eq!(classify(&anyhow::Error::new(RateLimitedError { retry_after: None, message: String::new() })), ErrorClass::RateLimited);
+        assert_eq!(classify(&serde_json::from_str::<serde_json::Value>("{").unwrap_err().into()), ErrorClass::ParseFailure);
+        assert_eq!(classify(&anyhow::anyhow!("Generation cancelled")), ErrorClass::Other);
+        assert!(ErrorClass::InvalidRequest.is_fatal() && !ErrorClass::Timeout.is_fatal());
+
+        let policy = RetryPolicy::new(Duration::from_millis(100), Duration::from_secs(1));
+        for (attempt, ceiling) in [(0, 100), (2, 400), (6, 1000)] {
+            let delay = policy.delay(attempt);
+            assert!(delay >= Duration::from_millis(ceiling / 2) && delay <= Duration::from_millis(ceiling));
+        }
+    }
+}
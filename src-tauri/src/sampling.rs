@@ -0,0 +1,155 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::llm_provider::CompletionRequest;
+
+/// How the temperature changes from batch to batch over a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TemperatureSchedule {
+    /// Move linearly from `start` on the first batch to `end` on the last
+    Linear { start: f32, end: f32 },
+    /// One temperature per batch; batches past the end keep the last value
+    PerBatch { temperatures: Vec<f32> },
+}
+
+impl TemperatureSchedule {
+    pub fn temperature(&self, batch_id: usize, total_batches: usize) -> Option<f32> {
+        match self {
+            TemperatureSchedule::Linear { start, end } => {
+                let progress = if total_batches > 1 {
+                    batch_id.min(total_batches - 1) as f32 / (total_batches - 1) as f32
+                } else {
+                    0.0
+                };
+                Some(start + (end - start) * progress)
+            }
+            TemperatureSchedule::PerBatch { temperatures } => {
+                temperatures.get(batch_id).or(temperatures.last()).copied()
+            }
+        }
+    }
+
+    fn temperatures(&self) -> Vec<f32> {
+        match self {
+            TemperatureSchedule::Linear { start, end } => vec![*start, *end],
+            TemperatureSchedule::PerBatch { temperatures } => temperatures.clone(),
+        }
+    }
+}
+
+/// Sampling parameters for generation requests; unset values use the backend's defaults
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingConfig {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    /// Ollama and OpenAI-compatible servers only
+    pub repeat_penalty: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub stop: Vec<String>,
+    /// Not supported by Anthropic
+    pub presence_penalty: Option<f32>,
+    /// Not supported by Anthropic
+    pub frequency_penalty: Option<f32>,
+    /// Overrides `temperature` batch by batch when set
+    pub temperature_schedule: Option<TemperatureSchedule>,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            temperature: Some(0.7),
+            top_p: Some(0.9),
+            top_k: Some(40),
+            repeat_penalty: None,
+            max_tokens: Some(4000),
+            stop: Vec::new(),
+            presence_penalty: None,
+            frequency_penalty: None,
+            temperature_schedule: None,
+        }
+    }
+}
+
+impl SamplingConfig {
+    /// Reject values that every backend would refuse
+    pub fn validate(&self) -> Result<()> {
+        let scheduled = self.temperature_schedule.iter().flat_map(TemperatureSchedule::temperatures);
+        for temperature in self.temperature.into_iter().chain(scheduled) {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(anyhow::anyhow!("Temperature must be between 0 and 2, got {}", temperature));
+            }
+        }
+        if let Some(top_p) = self.top_p.filter(|top_p| !(*top_p > 0.0 && *top_p <= 1.0)) {
+            return Err(anyhow::anyhow!("top_p must be in (0, 1], got {}", top_p));
+        }
+        for penalty in [self.presence_penalty, self.frequency_penalty].into_iter().flatten() {
+            if !(-2.0..=2.0).contains(&penalty) {
+                return Err(anyhow::anyhow!("Presence and frequency penalties must be between -2 and 2, got {}", penalty));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err(anyhow::anyhow!("max_tokens must be positive"));
+        }
+        Ok(())
+    }
+
+    /// The sampling for one batch, with the temperature schedule resolved
+    pub fn for_batch(&self, batch_id: usize, total_batches: usize) -> SamplingConfig {
+        let temperature = match &self.temperature_schedule {
+            Some(schedule) => schedule.temperature(batch_id, total_batches).or(self.temperature),
+            None => self.temperature,
+        };
+        SamplingConfig {
+            temperature,
+            temperature_schedule: None,
+            ..self.clone()
+        }
+    }
+
+    /// Copy these parameters onto a request
+    pub fn apply(&self, mut request: CompletionRequest) -> CompletionRequest {
+        request.temperature = self.temperature;
+        request.top_p = self.top_p;
+        request.top_k = self.top_k;
+        request.repeat_penalty = self.repeat_penalty;
+        request.max_tokens = self.max_tokens;
+        request.stop = self.stop.clone();
+        request.presence_penalty = self.presence_penalty;
+        request.frequency_penalty = self.frequency_penalty;
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedules_resolve_per_batch() {
+        let linear = SamplingConfig {
+            temperature_schedule: Some(TemperatureSchedule::Linear { start: 0.2, end: 1.0 }),
+            ..Default::default()
+        };
+        let temperatures: Vec<f32> = (0..5).map(|batch| linear.for_batch(batch, 5).temperature.unwrap()).collect();
+        assert!(temperatures.iter().zip([0.2, 0.4, 0.6, 0.8, 1.0]).all(|(a, b)| (a - b).abs() < 1e-6));
+        assert_eq!(linear.for_batch(0, 1).temperature, Some(0.2));
+
+        let per_batch = SamplingConfig {
+            temperature_schedule: Some(TemperatureSchedule::PerBatch { temperatures: vec![0.3, 0.9] }),
+            ..Default::default()
+        };
+        assert_eq!(per_batch.for_batch(1, 4).temperature, Some(0.9));
+        assert_eq!(per_batch.for_batch(3, 4).temperature, Some(0.9));
+        assert!(per_batch.for_batch(3, 4).temperature_schedule.is_none());
+
+        let too_hot = SamplingConfig {
+            temperature_schedule: Some(TemperatureSchedule::Linear { start: 0.5, end: 2.5 }),
+            ..Default::default()
+        };
+        assert!(too_hot.validate().is_err());
+        assert!(SamplingConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,329 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::llm_provider::OLLAMA_BASE_URL;
+use crate::usage::ModelPricing;
+use crate::rate_limiter::RateLimits;
+use crate::request_archive::ArchiveSettings;
+use crate::types::ModelProvider;
+
+const SETTINGS_FILE: &str = "provider_settings.json";
+/// Service name API keys are stored under in the OS credential store
+const KEYRING_SERVICE: &str = "dataset_generator";
+
+fn read_secret(account: &str) -> Option<String> {
+    match keyring::Entry::new(KEYRING_SERVICE, account).and_then(|entry| entry.get_password()) {
+        Ok(secret) => Some(secret),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            tracing::warn!("Could not read the stored key '{}': {}", account, e);
+            None
+        }
+    }
+}
+
+/// Store a secret, or delete it when `None`
+fn write_secret(account: &str, secret: Option<&str>) -> anyhow::Result<()> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, account)?;
+    match secret {
+        Some(secret) => entry.set_password(secret)?,
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(e.into()),
+        },
+    }
+    Ok(())
+}
+
+static APP_DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set the platform app data directory at startup, before any state is loaded
+pub fn init_data_dir(dir: PathBuf) {
+    if APP_DATA_DIR.set(dir).is_err() {
+        tracing::warn!("App data directory already set");
+    }
+}
+
+/// Directory for app-local state; `DATASET_GENERATOR_DATA_DIR` overrides the app data directory
+pub fn data_dir() -> PathBuf {
+    std::env::var("DATASET_GENERATOR_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| match APP_DATA_DIR.get() {
+            Some(dir) => dir.clone(),
+            // Only reached outside the app, e.g. in tests
+            None => std::env::current_dir()
+                .unwrap_or_else(|_| PathBuf::from("."))
+                .join(".dataset_generator"),
+        })
+}
+
+/// Accept the same forms as `OLLAMA_HOST` (`host`, `host:port`, full URL) and return a base URL
+pub fn normalize_ollama_host(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.starts_with("http://") || host.starts_with("https://") {
+        host.to_string()
+    } else if host.contains(':') {
+        format!("http://{}", host)
+    } else {
+        format!("http://{}:11434", host)
+    }
+}
+
+/// A self-hosted server exposing the OpenAI `/v1` API (vLLM, llama.cpp server, LM Studio, LocalAI)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAICompatibleEndpoint {
+    pub name: String,
+    pub base_url: String,
+    /// Kept in the OS credential store; never written to the settings file or sent to the UI
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<String>,
+}
+
+impl OpenAICompatibleEndpoint {
+    fn keyring_account(&self) -> String {
+        format!("openai_compatible:{}", self.name)
+    }
+
+    fn load_api_key(&mut self) {
+        if let Some(api_key) = read_secret(&self.keyring_account()) {
+            self.api_key = Some(api_key);
+        }
+    }
+
+    fn store_api_key(&self) -> anyhow::Result<()> {
+        write_secret(&self.keyring_account(), self.api_key.as_deref())
+    }
+}
+
+/// Credential store account and environment variable of a hosted provider's API key
+fn hosted_key_names(provider: &ModelProvider) -> Option<(&'static str, &'static str)> {
+    match provider {
+        ModelProvider::OpenAI => Some(("openai", "OPENAI_API_KEY")),
+        ModelProvider::Anthropic => Some(("anthropic", "ANTHROPIC_API_KEY")),
+        ModelProvider::Ollama | ModelProvider::OpenAICompatible => None,
+    }
+}
+
+/// Connection settings for the model providers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderSettings {
+    #[serde(default)]
+    pub openai_compatible_endpoints: Vec<OpenAICompatibleEndpoint>,
+    /// Override for the Anthropic API base URL (proxies, local mock servers)
+    #[serde(default)]
+    pub anthropic_base_url: Option<String>,
+    /// Ollama servers as base URLs; the first one is used for discovery, validation and embeddings
+    #[serde(default)]
+    pub ollama_hosts: Vec<String>,
+    /// OpenAI model ids always offered, even when /v1/models does not list them (e.g. fine-tunes)
+    #[serde(default)]
+    pub pinned_openai_models: Vec<String>,
+    /// Per-model prices overriding the built-in table, keyed by model id or id prefix
+    #[serde(default)]
+    pub model_pricing: BTreeMap<String, ModelPricing>,
+    /// Requests and tokens per minute allowed on the hosted API accounts; generation defaults apply when unset
+    #[serde(default)]
+    pub hosted_rate_limits: Option<RateLimits>,
+    /// Requests each Ollama server runs at once (its OLLAMA_NUM_PARALLEL); probed when unset
+    #[serde(default)]
+    pub ollama_num_parallel: Option<usize>,
+    /// Archiving of raw provider requests and responses for debugging runs
+    #[serde(default)]
+    pub request_archive: ArchiveSettings,
+    /// Hosted API keys from the OS credential store; never written to the settings file
+    #[serde(skip)]
+    openai_api_key: Option<String>,
+    #[serde(skip)]
+    anthropic_api_key: Option<String>,
+}
+
+impl ProviderSettings {
+    /// Load settings from environment variables
+    pub fn from_env() -> Self {
+        let mut settings = Self::default();
+        settings.apply_env();
+        settings
+    }
+
+    /// Load the saved settings file, filling anything it does not set from the environment
+    pub fn load() -> Self {
+        let path = data_dir().join(SETTINGS_FILE);
+        let mut settings: Self = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring unreadable settings file {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        // Files written by older versions hold the keys in plain text; move them to the credential store
+        let plaintext_keys = settings.openai_compatible_endpoints.iter().any(|endpoint| endpoint.api_key.is_some());
+        for endpoint in &mut settings.openai_compatible_endpoints {
+            if endpoint.api_key.is_none() {
+                endpoint.load_api_key();
+            }
+        }
+        if plaintext_keys {
+            if let Err(e) = settings.save() {
+                tracing::warn!("Failed to move endpoint API keys out of {:?}: {}", path, e);
+            }
+        }
+        settings.openai_api_key = read_secret("openai");
+        settings.anthropic_api_key = read_secret("anthropic");
+        settings.apply_env();
+        settings
+    }
+
+    /// Persist the settings so edits made from the UI survive restarts; API keys go to the credential store
+    pub fn save(&self) -> anyhow::Result<()> {
+        for endpoint in &self.openai_compatible_endpoints {
+            endpoint.store_api_key()?;
+        }
+        let dir = data_dir();
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(SETTINGS_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn apply_env(&mut self) {
+        if self.anthropic_base_url.is_none() {
+            self.anthropic_base_url = std::env::var("ANTHROPIC_BASE_URL").ok();
+        }
+
+        if self.ollama_hosts.is_empty() {
+            if let Ok(hosts) = std::env::var("OLLAMA_HOST") {
+                self.set_ollama_hosts(hosts.split(',').map(String::from).collect());
+            }
+        }
+
+        if self.ollama_num_parallel.is_none() {
+            self.ollama_num_parallel = std::env::var("OLLAMA_NUM_PARALLEL").ok().and_then(|value| value.parse().ok());
+        }
+
+        if let Ok(base_url) = std::env::var("OPENAI_COMPATIBLE_BASE_URL") {
+            let name = std::env::var("OPENAI_COMPATIBLE_NAME").unwrap_or_else(|_| "local".to_string());
+            if self.find_openai_compatible_endpoint(&name).is_none() {
+                self.openai_compatible_endpoints.push(OpenAICompatibleEndpoint {
+                    name,
+                    base_url,
+                    api_key: std::env::var("OPENAI_COMPATIBLE_API_KEY").ok(),
+                });
+            }
+        }
+    }
+
+    /// API key of a hosted provider: the stored key, else the provider's environment variable
+    pub fn api_key(&self, provider: &ModelProvider) -> Option<String> {
+        let (_, variable) = hosted_key_names(provider)?;
+        let stored = match provider {
+            ModelProvider::OpenAI => &self.openai_api_key,
+            _ => &self.anthropic_api_key,
+        };
+        stored
+            .clone()
+            .or_else(|| std::env::var(variable).ok())
+            .filter(|api_key| !api_key.trim().is_empty())
+    }
+
+    /// Store the API key of a hosted provider in the credential store, or delete it when `None`
+    pub fn set_api_key(&mut self, provider: &ModelProvider, api_key: Option<String>) -> anyhow::Result<()> {
+        let (account, _) = hosted_key_names(provider)
+            .ok_or_else(|| anyhow::anyhow!("{:?} has no account-wide API key", provider))?;
+        let api_key = api_key.map(|api_key| api_key.trim().to_string()).filter(|api_key| !api_key.is_empty());
+        write_secret(account, api_key.as_deref())?;
+        match provider {
+            ModelProvider::OpenAI => self.openai_api_key = api_key,
+            _ => self.anthropic_api_key = api_key,
+        }
+        Ok(())
+    }
+
+    /// Base URL of the primary Ollama server
+    pub fn ollama_base_url(&self) -> &str {
+        self.ollama_hosts.first().map(String::as_str).unwrap_or(OLLAMA_BASE_URL)
+    }
+
+    pub fn set_ollama_hosts(&mut self, hosts: Vec<String>) {
+        self.ollama_hosts = hosts
+            .iter()
+            .filter(|host| !host.trim().is_empty())
+            .map(|host| normalize_ollama_host(host))
+            .collect();
+        let mut seen = HashSet::new();
+        self.ollama_hosts.retain(|host| seen.insert(host.clone()));
+    }
+
+    pub fn find_openai_compatible_endpoint(&self, name: &str) -> Option<&OpenAICompatibleEndpoint> {
+        self.openai_compatible_endpoints.iter().find(|endpoint| endpoint.name == name)
+    }
+
+    /// Add an endpoint, replacing any existing endpoint with the same name
+    pub fn upsert_openai_compatible_endpoint(&mut self, endpoint: OpenAICompatibleEndpoint) {
+        self.openai_compatible_endpoints.retain(|existing| existing.name != endpoint.name);
+        self.openai_compatible_endpoints.push(endpoint);
+    }
+
+    /// Remove an endpoint and its stored API key
+    pub fn remove_openai_compatible_endpoint(&mut self, name: &str) -> bool {
+        let Some(index) = self.openai_compatible_endpoints.iter().position(|endpoint| endpoint.name == name) else {
+            return false;
+        };
+        let mut endpoint = self.openai_compatible_endpoints.remove(index);
+        endpoint.api_key = None;
+        if let Err(e) = endpoint.store_api_key() {
+            tracing::warn!("Failed to delete the API key for endpoint '{}': {}", name, e);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_ollama_host_accepts_ollama_host_forms() {
+        assert_eq!(normalize_ollama_host("gpu-box"), "http://gpu-box:11434");
+        assert_eq!(normalize_ollama_host(" 10.0.0.5:11500 "), "http://10.0.0.5:11500");
+        assert_eq!(normalize_ollama_host("https://ollama.example.com/"), "https://ollama.example.com");
+        assert_eq!(normalize_ollama_host("http://localhost:11434"), "http://localhost:11434");
+    }
+
+    #[test]
+    fn test_saved_settings_take_precedence_over_environment() {
+        std::env::set_var("OLLAMA_HOST", "gpu-box,localhost:11500,gpu-box:11434");
+        std::env::set_var("OLLAMA_NUM_PARALLEL", "4");
+
+        let from_env = ProviderSettings::from_env();
+        assert_eq!(from_env.ollama_hosts, ["http://gpu-box:11434", "http://localhost:11500"]);
+        assert_eq!(from_env.ollama_base_url(), "http://gpu-box:11434");
+        assert_eq!(from_env.ollama_num_parallel, Some(4));
+
+        let mut saved = ProviderSettings {
+            ollama_hosts: vec!["http://saved:11434".to_string()],
+            ollama_num_parallel: Some(2),
+            ..Default::default()
+        };
+        saved.apply_env();
+        assert_eq!(saved.ollama_hosts, ["http://saved:11434"]);
+        assert_eq!(saved.ollama_num_parallel, Some(2));
+
+        std::env::remove_var("OLLAMA_HOST");
+        std::env::remove_var("OLLAMA_NUM_PARALLEL");
+        assert_eq!(ProviderSettings::from_env().ollama_base_url(), OLLAMA_BASE_URL);
+    }
+
+    #[test]
+    fn test_stored_api_keys_take_precedence_over_environment() {
+        std::env::set_var("OPENAI_API_KEY", "sk-env");
+        let mut settings = ProviderSettings::from_env();
+        assert_eq!(settings.api_key(&ModelProvider::OpenAI).as_deref(), Some("sk-env"));
+        assert_eq!(settings.api_key(&ModelProvider::OpenAICompatible), None);
+
+        settings.openai_api_key = Some("sk-stored".to_string());
+        assert_eq!(settings.api_key(&ModelProvider::OpenAI).as_deref(), Some("sk-stored"));
+        assert!(settings.set_api_key(&ModelProvider::Ollama, Some("key".to_string())).is_err());
+        std::env::remove_var("OPENAI_API_KEY");
+    }
+}
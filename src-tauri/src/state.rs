@@ -2,9 +2,11 @@ use std::sync::Arc;
 use std::collections::HashMap;
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
-use crate::types::{Model, DatasetEntry, GenerationConfig, GenerationProgress};
+use crate::types::{Model, DatasetEntry, GenerationConfig, GenerationProgress, RunSummary};
 use crate::knowledge_base::KnowledgeBaseManager;
 use crate::chromadb_server::ChromaDbServerManager;
+use crate::settings::ProviderSettings;
+use crate::quarantine::QuarantineStore;
 
 pub struct AppState {
     pub models: Arc<RwLock<Vec<Model>>>,
@@ -14,6 +16,11 @@ pub struct AppState {
     pub active_generations: Arc<RwLock<HashMap<String, CancellationToken>>>,
     pub knowledge_base_manager: Arc<RwLock<Option<KnowledgeBaseManager>>>,
     pub chromadb_server: Arc<ChromaDbServerManager>,
+    pub provider_settings: Arc<RwLock<ProviderSettings>>,
+    /// Usage and outcome of finished runs, keyed by generation id
+    pub run_summaries: Arc<RwLock<HashMap<String, RunSummary>>>,
+    /// Responses that failed to parse, kept out of the dataset for review
+    pub quarantine: QuarantineStore,
 }
 
 impl AppState {
@@ -33,10 +40,14 @@ impl AppState {
                 entries_per_second: 0.0,
                 errors_count: 0,
                 retries_count: 0,
+                ..Default::default()
             })),
             active_generations: Arc::new(RwLock::new(HashMap::new())),
             knowledge_base_manager: Arc::new(RwLock::new(None)),
             chromadb_server: Arc::new(ChromaDbServerManager::new()),
+            provider_settings: Arc::new(RwLock::new(ProviderSettings::load())),
+            run_summaries: Arc::new(RwLock::new(HashMap::new())),
+            quarantine: QuarantineStore::new(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,312 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::types::{DatasetEntry, DatasetFormat};
+
+/// Key holding the generated entries in a structured batch response
+pub const ENTRIES_KEY: &str = "entries";
+
+/// A JSON Schema the model output must conform to
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub schema: Value,
+}
+
+impl ResponseSchema {
+    /// Schema for one generation batch: `{"entries": [<entry>, ...]}`
+    ///
+    /// The array is wrapped in an object because OpenAI's strict mode requires an object root.
+    pub fn for_batch(format: &DatasetFormat) -> Self {
+        Self {
+            name: format!("{}_batch", format_name(format)),
+            schema: json!({
+                "type": "object",
+                "properties": {
+                    ENTRIES_KEY: {
+                        "type": "array",
+                        "items": entry_schema(format)
+                    }
+                },
+                "required": [ENTRIES_KEY],
+                "additionalProperties": false
+            }),
+        }
+    }
+}
+
+/// Outcome of parsing one model response into dataset entries
+#[derive(Debug, Clone)]
+pub struct ParsedBatch {
+    pub entries: Vec<DatasetEntry>,
+    /// Backend configuration the response was generated with, when the provider reports it
+    pub system_fingerprint: Option<String>,
+}
+
+/// Pull the entries out of a structured batch response, also accepting a bare array
+pub fn extract_entries(value: Value) -> Option<Vec<Value>> {
+    match value {
+        Value::Array(entries) => Some(entries),
+        Value::Object(mut object) => match object.remove(ENTRIES_KEY) {
+            Some(Value::Array(entries)) => Some(entries),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Picks complete entry objects out of a JSON response while it is still streaming
+///
+/// Entries are the objects directly inside the first array of the response, which covers
+/// both a bare `[...]` and the structured `{"entries": [...]}` shape.
+#[derive(Debug, Default)]
+pub struct IncrementalEntryParser {
+    buffer: String,
+    scanned: usize,
+    /// Open brackets, as bytes
+    stack: Vec<u8>,
+    in_string: bool,
+    escaped: bool,
+    /// Stack depth just inside the entries array, once it has been opened
+    entries_depth: Option<usize>,
+    entry_start: Option<usize>,
+}
+
+impl IncrementalEntryParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append streamed text and return the entries completed by it
+    pub fn push(&mut self, chunk: &str) -> Vec<Value> {
+        self.buffer.push_str(chunk);
+        let mut completed = Vec::new();
+
+        // Structural characters are ASCII, so scanning bytes is safe with multi-byte text
+        let bytes = self.buffer.as_bytes();
+        for (index, &byte) in bytes.iter().enumerate().skip(self.scanned) {
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => self.in_string = true,
+                b'[' | b'{' => {
+                    if byte == b'{' && self.entries_depth == Some(self.stack.len()) {
+                        self.entry_start = Some(index);
+                    }
+                    self.stack.push(byte);
+                    if byte == b'[' && self.entries_depth.is_none() {
+                        self.entries_depth = Some(self.stack.len());
+                    }
+                }
+                b']' | b'}' => {
+                    self.stack.pop();
+                    if byte == b'}' && self.entries_depth == Some(self.stack.len()) {
+                        if let Some(start) = self.entry_start.take() {
+                            if let Ok(entry) = serde_json::from_str(&self.buffer[start..=index]) {
+                                completed.push(entry);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.scanned = self.buffer.len();
+
+        completed
+    }
+}
+
+/// Rough completion tokens one entry of the format takes, JSON syntax included
+pub fn estimated_entry_tokens(format: &DatasetFormat) -> u32 {
+    match format {
+        DatasetFormat::Alpaca | DatasetFormat::FunctionCall => 300,
+        DatasetFormat::ChainOfThought
+        | DatasetFormat::PreferenceRanking
+        | DatasetFormat::RetrievalEmbedding
+        | DatasetFormat::Reranking => 500,
+        DatasetFormat::Conversation | DatasetFormat::CodeTask => 600,
+        DatasetFormat::Reflection => 700,
+        DatasetFormat::MultiRoundDialogue => 800,
+    }
+}
+
+/// Largest batch, up to `requested`, whose entries fit in `max_completion_tokens`; at least one entry
+///
+/// A batch that does not fit is cut off mid-response and cannot be parsed, however often it is retried.
+pub fn fit_batch_size(requested: usize, max_completion_tokens: u32, format: &DatasetFormat) -> usize {
+    let fitting = (max_completion_tokens / estimated_entry_tokens(format)) as usize;
+    requested.min(fitting).max(1)
+}
+
+/// JSON Schema for a single entry of the given format
+///
+/// Every object lists all of its properties as required and disallows extras so the
+/// schema is accepted by OpenAI's strict structured outputs.
+pub fn entry_schema(format: &DatasetFormat) -> Value {
+    match format {
+        DatasetFormat::Alpaca => object(&[
+            ("instruction", string()),
+            ("input", string()),
+            ("output", string()),
+        ]),
+        DatasetFormat::Conversation => object(&[("messages", messages())]),
+        DatasetFormat::ChainOfThought => object(&[
+            ("question", string()),
+            ("answer", string()),
+        ]),
+        DatasetFormat::PreferenceRanking => object(&[
+            ("prompt", string()),
+            ("chosen", string()),
+            ("rejected", string()),
+        ]),
+        DatasetFormat::FunctionCall => object(&[
+            ("messages", messages()),
+            ("function", object(&[
+                ("name", string()),
+                // JSON-encoded arguments, as in OpenAI tool calls
+                ("arguments", string()),
+            ])),
+        ]),
+        DatasetFormat::MultiRoundDialogue => object(&[
+            ("instruction", string()),
+            ("conversation", messages()),
+        ]),
+        DatasetFormat::CodeTask => object(&[
+            ("prompt", string()),
+            ("code", string()),
+            ("output", string()),
+        ]),
+        DatasetFormat::Reflection => object(&[
+            ("instruction", string()),
+            ("output", string()),
+            ("reflection", string()),
+            ("corrected", string()),
+        ]),
+        DatasetFormat::RetrievalEmbedding => object(&[
+            ("query", string()),
+            ("positive_passage", string()),
+            ("negative_passages", array(string())),
+        ]),
+        DatasetFormat::Reranking => object(&[
+            ("query", string()),
+            ("documents", array(string())),
+            ("relevance_scores", array(json!({ "type": "number" }))),
+        ]),
+    }
+}
+
+/// The format's serialized name, e.g. `chain_of_thought`
+fn format_name(format: &DatasetFormat) -> String {
+    serde_json::to_value(format)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_else(|| "dataset".to_string())
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn messages() -> Value {
+    array(object(&[
+        ("role", json!({ "type": "string", "enum": ["system", "user", "assistant"] })),
+        ("content", string()),
+    ]))
+}
+
+fn object(properties: &[(&str, Value)]) -> Value {
+    let required: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+    let properties: serde_json::Map<String, Value> = properties
+        .iter()
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_entries_accepts_wrapper_and_bare_array() {
+        let wrapped = json!({ "entries": [{ "instruction": "a" }] });
+        assert_eq!(extract_entries(wrapped).unwrap().len(), 1);
+        assert_eq!(extract_entries(json!([{}, {}])).unwrap().len(), 2);
+        assert!(extract_entries(json!({ "instruction": "a" })).is_none());
+    }
+
+    #[test]
+    fn test_incremental_parser_emits_entries_as_they_complete() {
+        let mut parser = IncrementalEntryParser::new();
+        assert!(parser.push(r#"{"entries": [{"instruction": "say \"hi\" {"#).is_empty());
+        let first = parser.push(r#"", "output": "[ok]"}, {"instruction": "#);
+        assert_eq!(first, vec![json!({"instruction": "say \"hi\" {", "output": "[ok]"})]);
+        let second = parser.push(r#""two", "tags": [{"a": 1}]}]}"#);
+        assert_eq!(second, vec![json!({"instruction": "two", "tags": [{"a": 1}]})]);
+
+        let mut bare = IncrementalEntryParser::new();
+        assert_eq!(bare.push(r#"Here you go: [{"query": "é"}, {"query": "b"}]"#).len(), 2);
+    }
+
+    #[test]
+    fn test_batch_schema_is_strict_for_every_format() {
+        let formats = [
+            DatasetFormat::Alpaca,
+            DatasetFormat::Conversation,
+            DatasetFormat::ChainOfThought,
+            DatasetFormat::PreferenceRanking,
+            DatasetFormat::FunctionCall,
+            DatasetFormat::MultiRoundDialogue,
+            DatasetFormat::CodeTask,
+            DatasetFormat::Reflection,
+            DatasetFormat::RetrievalEmbedding,
+            DatasetFormat::Reranking,
+        ];
+
+        fn assert_strict(schema: &Value) {
+            if schema["type"] == "object" {
+                let properties = schema["properties"].as_object().unwrap();
+                let required: Vec<&str> = schema["required"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+                assert_eq!(properties.len(), required.len());
+                assert!(properties.keys().all(|key| required.contains(&key.as_str())));
+                assert_eq!(schema["additionalProperties"], false);
+                properties.values().for_each(assert_strict);
+            } else if schema["type"] == "array" {
+                assert_strict(&schema["items"]);
+            }
+        }
+
+        for format in &formats {
+            let schema = ResponseSchema::for_batch(format);
+            assert!(schema.name.ends_with("_batch"));
+            assert_strict(&schema.schema);
+        }
+        assert_eq!(ResponseSchema::for_batch(&DatasetFormat::ChainOfThought).name, "chain_of_thought_batch");
+    }
+
+    #[test]
+    fn test_batch_size_fits_the_completion_limit() {
+        assert_eq!(fit_batch_size(10, 2048, &DatasetFormat::Alpaca), 6);
+        assert_eq!(fit_batch_size(4, 65_536, &DatasetFormat::Alpaca), 4);
+        assert_eq!(fit_batch_size(10, 512, &DatasetFormat::MultiRoundDialogue), 1);
+    }
+}
@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::retry::ErrorClass;
+use crate::sampling::SamplingConfig;
+use crate::usage::{BudgetLimit, GenerationBudget, UsageSummary};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Model {
@@ -8,12 +13,52 @@ pub struct Model {
     pub modified: String,
     pub provider: ModelProvider,
     pub capabilities: Vec<String>,
+    /// Configured endpoint serving this model, for providers with several servers
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub details: ModelDetails,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Metadata probed from the serving backend (Ollama /api/show); empty for hosted APIs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelDetails {
+    pub size_bytes: Option<u64>,
+    pub context_length: Option<u64>,
+    pub parameter_count: Option<u64>,
+    pub quantization: Option<String>,
+    pub family: Option<String>,
+    pub template: Option<String>,
+}
+
+impl Model {
+    /// Model name to send to the provider API; endpoint models are namespaced as `endpoint/model`
+    pub fn api_model_id(&self) -> &str {
+        self.endpoint
+            .as_deref()
+            .and_then(|endpoint| self.id.strip_prefix(endpoint))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .unwrap_or(&self.id)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Hash, Eq, PartialEq)]
 pub enum ModelProvider {
+    #[default]
     Ollama,
     OpenAI,
+    OpenAICompatible,
+    Anthropic,
+}
+
+/// Result of probing an Ollama server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaConnectionStatus {
+    pub base_url: String,
+    pub reachable: bool,
+    pub version: Option<String>,
+    pub loaded_models: Vec<String>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
@@ -44,6 +89,9 @@ pub enum DatasetFormat {
 pub struct DatasetEntry {
     #[serde(flatten)]
     pub data: serde_json::Value,
+    /// Model that generated the entry; not part of the exported training data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_model: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,9 +102,59 @@ pub struct GenerationConfig {
     pub fine_tuning_goal: String,
     pub domain_context: String,
     pub format: DatasetFormat,
+    /// Stream model output and emit entries to the frontend as they are parsed
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub budget: GenerationBudget,
+    /// Models to draw batches from, in proportion to their weights; empty uses `selected_model` alone
+    #[serde(default)]
+    pub model_mix: Vec<ModelWeight>,
+    /// Models to reroute a batch to, in order, once it has failed on its own model
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    /// Always call the provider instead of reusing cached responses to identical requests
+    #[serde(default)]
+    pub bypass_response_cache: bool,
+    /// Seed for reproducible runs; each batch derives its own seed from it
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub sampling: SamplingConfig,
+}
+
+/// A model's share of the batches in a mixed-model run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelWeight {
+    pub model_id: String,
+    pub weight: f32,
+}
+
+/// Tauri event carrying one entry parsed from a streaming response
+///
+/// Streamed entries are provisional: they have not been filtered or deduplicated,
+/// and the run's final dataset replaces them when it completes.
+pub const GENERATION_ENTRY_EVENT: &str = "generation-entry";
+/// Tauri event withdrawing the entries streamed by a batch attempt that failed or will be retried
+pub const GENERATION_BATCH_DISCARDED_EVENT: &str = "generation-batch-discarded";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationEntryEvent {
+    pub generation_id: String,
+    pub batch_id: usize,
+    /// Attempt of the batch that streamed the entry
+    pub attempt_id: String,
+    pub entry: DatasetEntry,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationBatchDiscardedEvent {
+    pub generation_id: String,
+    pub batch_id: usize,
+    pub attempt_id: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GenerationProgress {
     pub current_batch: usize,
     pub total_batches: usize,
@@ -68,6 +166,36 @@ pub struct GenerationProgress {
     pub entries_per_second: f64,
     pub errors_count: usize,
     pub retries_count: usize,
+    /// Share of model responses that parsed as JSON, across the run
+    pub parse_success_rate: f32,
+    /// Parse success rate of each completed batch, keyed by batch id
+    pub batch_parse_success_rates: BTreeMap<usize, f32>,
+    /// Tokens spent so far and their estimated cost
+    pub usage: UsageSummary,
+    /// The budget limit that stopped the run, when status is "budget_exhausted"
+    pub budget_exhausted: Option<BudgetLimit>,
+    /// Batches that were rerouted to a fallback model, keyed by batch id
+    pub model_substitutions: BTreeMap<usize, ModelSubstitution>,
+    /// Distinct system fingerprints reported by the provider, identifying the backend configuration
+    pub system_fingerprints: BTreeSet<String>,
+    /// Failed batches counted by the class of error that failed them
+    pub error_classes: BTreeMap<ErrorClass, usize>,
+    /// Responses that failed to parse and were quarantined instead of added to the dataset
+    pub quarantined_responses: usize,
+}
+
+/// Final accounting of a finished, failed or cancelled generation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub generation_id: String,
+    pub model: String,
+    pub status: String,
+    pub entries_generated: usize,
+    pub duration_secs: f64,
+    pub usage: UsageSummary,
+    /// Seed the run was generated with, for reproducing it
+    pub seed: Option<u64>,
+    pub system_fingerprints: BTreeSet<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -77,8 +205,34 @@ pub struct GenerationTask {
     pub entries_to_generate: usize,
     pub model_id: String,
     pub provider: ModelProvider,
+    pub endpoint: Option<String>,
     pub goal: String,
+    /// Domain the entries should be drawn from, as described by the user
+    pub domain_context: String,
     pub context: String,
+    pub seed: Option<u64>,
+    /// Sampling for this batch, with any temperature schedule already applied
+    pub sampling: SamplingConfig,
+}
+
+#[cfg(test)]
+impl GenerationTask {
+    /// A ten-entry Ollama task for tests, with no seed or context
+    pub fn for_test(batch_id: usize, model_id: &str) -> Self {
+        Self {
+            id: batch_id.to_string(),
+            batch_id,
+            entries_to_generate: 10,
+            model_id: model_id.to_string(),
+            provider: ModelProvider::Ollama,
+            endpoint: None,
+            goal: "goal".to_string(),
+            domain_context: String::new(),
+            context: String::new(),
+            seed: None,
+            sampling: SamplingConfig::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -87,4 +241,30 @@ pub struct BatchResult {
     pub entries: Vec<DatasetEntry>,
     pub generation_time: std::time::Duration,
     pub retry_count: usize,
+    /// Model responses behind this batch and how many of them parsed
+    pub responses: usize,
+    pub parsed_responses: usize,
+    /// Set when the batch was produced by a fallback model
+    pub substitution: Option<ModelSubstitution>,
+    pub system_fingerprints: BTreeSet<String>,
+}
+
+/// A batch rerouted to a fallback model after failing on its assigned one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelSubstitution {
+    pub original_model: String,
+    pub model: String,
+    pub provider: ModelProvider,
+    /// The error that exhausted the previous model
+    pub reason: String,
+}
+
+impl BatchResult {
+    pub fn parse_success_rate(&self) -> f32 {
+        if self.responses == 0 {
+            0.0
+        } else {
+            self.parsed_responses as f32 / self.responses as f32
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use crate::llm_provider::TokenUsage;
+use crate::types::ModelProvider;
+
+/// USD list price per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+impl ModelPricing {
+    pub const fn new(prompt_per_million: f64, completion_per_million: f64) -> Self {
+        Self { prompt_per_million, completion_per_million }
+    }
+
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt_per_million
+            + usage.completion_tokens as f64 * self.completion_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Built-in prices for hosted models, matched by model id prefix
+const DEFAULT_PRICES: &[(&str, ModelPricing)] = &[
+    ("gpt-4o", ModelPricing::new(2.50, 10.00)),
+    ("gpt-4o-mini", ModelPricing::new(0.15, 0.60)),
+    ("gpt-4.1", ModelPricing::new(2.00, 8.00)),
+    ("gpt-4.1-mini", ModelPricing::new(0.40, 1.60)),
+    ("gpt-4.1-nano", ModelPricing::new(0.10, 0.40)),
+    ("gpt-4-turbo", ModelPricing::new(10.00, 30.00)),
+    ("gpt-4", ModelPricing::new(30.00, 60.00)),
+    ("gpt-3.5-turbo", ModelPricing::new(0.50, 1.50)),
+    ("o1", ModelPricing::new(15.00, 60.00)),
+    ("o1-mini", ModelPricing::new(1.10, 4.40)),
+    ("o1-pro", ModelPricing::new(150.00, 600.00)),
+    ("o3", ModelPricing::new(2.00, 8.00)),
+    ("o3-mini", ModelPricing::new(1.10, 4.40)),
+    ("o3-pro", ModelPricing::new(20.00, 80.00)),
+    ("o4-mini", ModelPricing::new(1.10, 4.40)),
+    ("gpt-5", ModelPricing::new(1.25, 10.00)),
+    ("gpt-5-mini", ModelPricing::new(0.25, 2.00)),
+    ("gpt-5-nano", ModelPricing::new(0.05, 0.40)),
+    ("claude-3-haiku", ModelPricing::new(0.25, 1.25)),
+    ("claude-3-5-haiku", ModelPricing::new(0.80, 4.00)),
+    ("claude-3-5-sonnet", ModelPricing::new(3.00, 15.00)),
+    ("claude-3-7-sonnet", ModelPricing::new(3.00, 15.00)),
+    ("claude-sonnet-4", ModelPricing::new(3.00, 15.00)),
+    ("claude-3-opus", ModelPricing::new(15.00, 75.00)),
+    ("claude-opus-4", ModelPricing::new(15.00, 75.00)),
+];
+
+/// Looks up model prices, preferring user-configured entries over the built-in list
+#[derive(Debug, Clone, Default)]
+pub struct PriceTable {
+    overrides: BTreeMap<String, ModelPricing>,
+}
+
+impl PriceTable {
+    pub fn new(overrides: BTreeMap<String, ModelPricing>) -> Self {
+        Self { overrides }
+    }
+
+    /// Built-in prices with the configured ones applied on top
+    pub fn entries(&self) -> BTreeMap<String, ModelPricing> {
+        let mut entries: BTreeMap<String, ModelPricing> = DEFAULT_PRICES
+            .iter()
+            .map(|(model, pricing)| (model.to_string(), *pricing))
+            .collect();
+        entries.extend(self.overrides.clone());
+        entries
+    }
+
+    /// Price for a model; local Ollama models are free unless configured, unknown models have no price
+    pub fn price(&self, provider: &ModelProvider, model: &str) -> Option<ModelPricing> {
+        if let Some(pricing) = self.overrides.get(model) {
+            return Some(*pricing);
+        }
+        if *provider == ModelProvider::Ollama {
+            return Some(ModelPricing::new(0.0, 0.0));
+        }
+
+        // Longest prefix wins so dated ids like gpt-4o-mini-2024-07-18 resolve to gpt-4o-mini
+        self.overrides
+            .iter()
+            .map(|(prefix, pricing)| (prefix.as_str(), *pricing))
+            .chain(DEFAULT_PRICES.iter().copied())
+            .filter(|(prefix, _)| model.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, pricing)| pricing)
+    }
+}
+
+/// Tokens spent on one model during a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub requests: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// None when the model has no known price
+    pub estimated_cost_usd: Option<f64>,
+}
+
+/// Token usage of a generation run, per model and per batch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Cost of the tokens spent on models with a known price; None until such a model is used
+    ///
+    /// Models without a price show a None cost in `by_model` and are left out of this total.
+    pub estimated_cost_usd: Option<f64>,
+    pub by_model: BTreeMap<String, ModelUsage>,
+    pub by_batch: BTreeMap<usize, TokenUsage>,
+    /// Models used without a known price, whose tokens a cost budget does not see
+    pub unpriced_models: BTreeSet<String>,
+}
+
+impl UsageSummary {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Add the usage of a batch's responses to the run totals
+    pub fn record(
+        &mut self,
+        batch_id: usize,
+        provider: &ModelProvider,
+        model: &str,
+        usage: &TokenUsage,
+        prices: &PriceTable,
+    ) {
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.by_batch.entry(batch_id).or_default().add(usage);
+
+        let model_usage = self.by_model.entry(model.to_string()).or_insert_with(|| ModelUsage {
+            estimated_cost_usd: Some(0.0),
+            ..Default::default()
+        });
+        model_usage.requests += 1;
+        model_usage.prompt_tokens += usage.prompt_tokens;
+        model_usage.completion_tokens += usage.completion_tokens;
+        model_usage.estimated_cost_usd = match (model_usage.estimated_cost_usd, prices.price(provider, model)) {
+            (Some(cost), Some(pricing)) => Some(cost + pricing.cost(usage)),
+            _ => None,
+        };
+        if model_usage.estimated_cost_usd.is_none() {
+            self.unpriced_models.insert(model.to_string());
+        }
+
+        let mut priced_costs = self.by_model.values().filter_map(|model_usage| model_usage.estimated_cost_usd).peekable();
+        self.estimated_cost_usd = priced_costs.peek().is_some().then(|| priced_costs.sum());
+    }
+}
+
+/// Limits that stop a generation run early, keeping the entries produced so far
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerationBudget {
+    pub max_total_tokens: Option<u64>,
+    /// Checked against the cost of models with a known price; tokens of unpriced models are not counted
+    pub max_cost_usd: Option<f64>,
+    pub max_duration_secs: Option<u64>,
+}
+
+/// The budget limit that ended a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+    Tokens,
+    Cost,
+    Time,
+}
+
+impl GenerationBudget {
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration_secs.map(Duration::from_secs)
+    }
+
+    /// The first limit reached by a run with this usage and running time
+    pub fn exceeded(&self, usage: &UsageSummary, elapsed: Duration) -> Option<BudgetLimit> {
+        let over_cost = match (self.max_cost_usd, usage.estimated_cost_usd) {
+            (Some(max), Some(cost)) => cost >= max,
+            _ => false,
+        };
+
+        if self.max_total_tokens.is_some_and(|max| usage.total_tokens() >= max) {
+            Some(BudgetLimit::Tokens)
+        } else if over_cost {
+            Some(BudgetLimit::Cost)
+        } else if self.max_duration().is_some_and(|max| elapsed >= max) {
+            Some(BudgetLimit::Time)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_lookup_prefers_overrides_and_longest_prefix() {
+        let table = PriceTable::new(BTreeMap::from([(
+            "ft:gpt-4o-mini:acme".to_string(),
+            ModelPricing::new(0.30, 1.20),
+        )]));
+
+        assert_eq!(table.price(&ModelProvider::OpenAI, "gpt-4o-mini-2024-07-18"), Some(ModelPricing::new(0.15, 0.60)));
+        assert_eq!(table.price(&ModelProvider::OpenAI, "gpt-4o-2024-08-06"), Some(ModelPricing::new(2.50, 10.00)));
+        assert_eq!(table.price(&ModelProvider::OpenAI, "ft:gpt-4o-mini:acme:v2"), Some(ModelPricing::new(0.30, 1.20)));
+        assert_eq!(table.price(&ModelProvider::Ollama, "llama3.2"), Some(ModelPricing::new(0.0, 0.0)));
+        assert_eq!(table.price(&ModelProvider::OpenAICompatible, "mistral-7b"), None);
+        assert_eq!(table.price(&ModelProvider::OpenAI, "o3-2025-04-16"), Some(ModelPricing::new(2.00, 8.00)));
+        assert_eq!(table.price(&ModelProvider::OpenAI, "o4-mini"), Some(ModelPricing::new(1.10, 4.40)));
+        assert_eq!(table.price(&ModelProvider::OpenAI, "gpt-5-mini-2025-08-07"), Some(ModelPricing::new(0.25, 2.00)));
+    }
+
+    #[test]
+    fn test_summary_accumulates_tokens_and_cost() {
+        let table = PriceTable::default();
+        let mut summary = UsageSummary::default();
+        let usage = TokenUsage { prompt_tokens: 1_000_000, completion_tokens: 500_000 };
+
+        summary.record(0, &ModelProvider::OpenAI, "gpt-4o-mini", &usage, &table);
+        summary.record(1, &ModelProvider::Ollama, "llama3.2", &usage, &table);
+        assert_eq!(summary.total_tokens(), 3_000_000);
+        assert_eq!(summary.by_model["gpt-4o-mini"].requests, 1);
+        assert!((summary.estimated_cost_usd.unwrap() - 0.45).abs() < 1e-9);
+        assert!(summary.unpriced_models.is_empty());
+
+        summary.record(2, &ModelProvider::OpenAICompatible, "mistral-7b", &usage, &table);
+        assert_eq!(summary.by_batch.len(), 3);
+        assert_eq!(summary.by_model["mistral-7b"].estimated_cost_usd, None);
+        assert_eq!(summary.unpriced_models, BTreeSet::from(["mistral-7b".to_string()]));
+        assert!((summary.estimated_cost_usd.unwrap() - 0.45).abs() < 1e-9);
+
+        let mut unpriced = UsageSummary::default();
+        unpriced.record(0, &ModelProvider::OpenAICompatible, "mistral-7b", &usage, &table);
+        assert_eq!(unpriced.estimated_cost_usd, None);
+    }
+
+    #[test]
+    fn test_budget_reports_first_limit_reached() {
+        let mut summary = UsageSummary::default();
+        let usage = TokenUsage { prompt_tokens: 600_000, completion_tokens: 400_000 };
+        summary.record(0, &ModelProvider::OpenAI, "gpt-4o", &usage, &PriceTable::default());
+
+        let budget = GenerationBudget { max_total_tokens: Some(2_000_000), max_cost_usd: Some(5.0), max_duration_secs: Some(60) };
+        assert_eq!(budget.exceeded(&summary, Duration::from_secs(1)), Some(BudgetLimit::Cost));
+        summary.record(1, &ModelProvider::OpenAICompatible, "mistral-7b", &usage, &PriceTable::default());
+        let cost_only = GenerationBudget { max_cost_usd: Some(5.0), ..Default::default() };
+        assert_eq!(cost_only.exceeded(&summary, Duration::from_secs(1)), Some(BudgetLimit::Cost));
+        assert_eq!(budget.exceeded(&UsageSummary::default(), Duration::from_secs(60)), Some(BudgetLimit::Time));
+        assert_eq!(GenerationBudget::default().exceeded(&summary, Duration::from_secs(3600)), None);
+    }
+}
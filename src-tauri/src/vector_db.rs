@@ -34,13 +34,16 @@ pub struct QueryRequest {
 pub struct VectorDbService {
     client: reqwest::Client,
     base_url: String,
+    ollama_base_url: String,
 }
 
 impl VectorDbService {
-    pub fn new(base_url: Option<String>) -> Self {
+    /// Connect to ChromaDB at `base_url`, embedding queries on the Ollama server at `ollama_base_url`
+    pub fn new(base_url: Option<String>, ollama_base_url: &str) -> Self {
         Self {
             client: reqwest::Client::new(),
             base_url: base_url.unwrap_or_else(|| "http://localhost:8465".to_string()),
+            ollama_base_url: ollama_base_url.to_string(),
         }
     }
 
@@ -296,7 +299,7 @@ impl VectorDbService {
         });
 
         let response = self.client
-            .post("http://localhost:11434/api/embeddings")
+            .post(format!("{}/api/embeddings", self.ollama_base_url))
             .json(&request_body)
             .send()
             .await?;
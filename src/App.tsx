@@ -20,6 +20,7 @@ const App: React.FC = () => {
     isDiscovering,
     isGenerating,
     progress,
+    liveEntries,
     currentStep,
     generationConfig,
     error,
@@ -63,6 +64,7 @@ const App: React.FC = () => {
             progress={progress}
             config={generationConfig}
             isGenerating={isGenerating}
+            liveEntries={liveEntries}
           />
         );
 
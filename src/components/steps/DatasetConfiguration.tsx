@@ -391,6 +391,25 @@ export const DatasetConfiguration: React.FC<DatasetConfigurationProps> = ({
                   ))}
                 </div>
               </div>
+
+              {/* Live Streaming */}
+              <div className="flex items-start space-x-3 rounded-lg border border-border bg-muted/30 p-4">
+                <input
+                  id="stream"
+                  type="checkbox"
+                  checked={config.stream ?? false}
+                  onChange={(e) => onConfigChange({ stream: e.target.checked })}
+                  className="mt-1 h-4 w-4 accent-green-500"
+                />
+                <div className="space-y-1">
+                  <Label htmlFor="stream" className="text-base font-semibold text-foreground">
+                    Stream entries live
+                  </Label>
+                  <p className="text-sm text-muted-foreground">
+                    Preview entries while batches are still generating
+                  </p>
+                </div>
+              </div>
             </CardContent>
           </Card>
         </div>
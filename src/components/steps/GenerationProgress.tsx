@@ -9,21 +9,28 @@ import {
   CheckCircle, 
   Loader2,
   BarChart3,
-  Activity
+  Activity,
+  Radio,
+  AlertTriangle
 } from 'lucide-react';
-import { GenerationProgress as ProgressType, GenerationConfig } from '@/types';
+import { GenerationProgress as ProgressType, GenerationConfig, GenerationEntryEvent } from '@/types';
+import { entryFields } from '@/lib/live-entries';
 import { cn } from '@/lib/utils';
 
 interface GenerationProgressProps {
   progress: ProgressType | null;
   config: GenerationConfig;
   isGenerating: boolean;
+  liveEntries: GenerationEntryEvent[];
 }
 
+const LIVE_PREVIEW_SIZE = 5;
+
 export const GenerationProgress: React.FC<GenerationProgressProps> = ({
   progress,
   config,
   isGenerating,
+  liveEntries,
 }) => {
   if (!progress) {
     return (
@@ -75,6 +82,8 @@ export const GenerationProgress: React.FC<GenerationProgressProps> = ({
   };
 
   const StatusIcon = getStatusIcon();
+  const unpricedModels = progress.usage?.unpriced_models ?? [];
+  const costBudgetIgnoresModels = config.budget?.max_cost_usd != null && unpricedModels.length > 0;
 
   return (
     <div className="space-y-8">
@@ -208,6 +217,49 @@ export const GenerationProgress: React.FC<GenerationProgressProps> = ({
         </CardContent>
       </Card>
 
+      {/* Unpriced models are invisible to the cost budget */}
+      {costBudgetIgnoresModels && (
+        <Card className="border-orange-500/50 bg-orange-500/5">
+          <CardContent className="p-4">
+            <div className="flex items-center space-x-3">
+              <AlertTriangle className="w-5 h-5 text-orange-500" />
+              <p className="text-sm text-foreground">
+                No price is known for {unpricedModels.join(', ')}. Their tokens do not count towards
+                the ${config.budget?.max_cost_usd} cost budget until a price is configured for them.
+              </p>
+            </div>
+          </CardContent>
+        </Card>
+      )}
+
+      {/* Live Preview */}
+      {isRunning && liveEntries.length > 0 && (
+        <Card className="border-border bg-card/50 backdrop-blur-sm shadow-lg">
+          <CardHeader className="pb-4">
+            <CardTitle className="text-foreground flex items-center text-xl">
+              <div className="p-2 bg-blue-500/10 rounded-lg mr-3">
+                <Radio className="w-5 h-5 text-blue-500" />
+              </div>
+              Live Preview
+            </CardTitle>
+            <CardDescription className="text-base">
+              {liveEntries.length.toLocaleString()} entries streamed so far. Entries are provisional until
+              validation and deduplication finish.
+            </CardDescription>
+          </CardHeader>
+          <CardContent className="space-y-3">
+            {liveEntries.slice(-LIVE_PREVIEW_SIZE).reverse().map((event, index) => (
+              <div key={`${event.attempt_id}-${liveEntries.length - index}`} className="rounded-lg border border-border bg-muted/30 p-3">
+                <div className="text-xs text-muted-foreground mb-2">Batch {event.batch_id + 1}</div>
+                <pre className="text-xs text-foreground whitespace-pre-wrap break-words max-h-40 overflow-hidden">
+                  {JSON.stringify(entryFields(event.entry), null, 2)}
+                </pre>
+              </div>
+            ))}
+          </CardContent>
+        </Card>
+      )}
+
       {/* Completion Message */}
       {isCompleted && (
         <Card className="border-green-500/50 bg-green-500/5 shadow-green-500/20 shadow-lg">
@@ -1,15 +1,19 @@
 import { useState, useEffect, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { save } from "@tauri-apps/plugin-dialog";
 import { writeTextFile } from "@tauri-apps/plugin-fs";
 import {
   Model,
   GenerationConfig,
   GenerationProgress,
+  GenerationEntryEvent,
+  GenerationBatchDiscardedEvent,
   Step,
   AppState,
 } from "@/types";
 import { getFormatInfo } from "@/lib/dataset-formats";
+import { appendLiveEntry, discardLiveAttempt } from "@/lib/live-entries";
 
 export const useDatasetGenerator = () => {
   const [state, setState] = useState<AppState>({
@@ -18,6 +22,7 @@ export const useDatasetGenerator = () => {
     isDiscovering: false,
     isGenerating: false,
     progress: null,
+    liveEntries: [],
     currentStep: "models",
     generationConfig: {
       target_entries: 2000,
@@ -25,6 +30,7 @@ export const useDatasetGenerator = () => {
       fine_tuning_goal: "",
       domain_context: "",
       format: "alpaca",
+      stream: true,
     },
     error: null,
     success: null,
@@ -50,13 +56,28 @@ export const useDatasetGenerator = () => {
           )) as GenerationProgress;
           setState((prev) => ({ ...prev, progress: currentProgress }));
 
-          if (currentProgress.status === "completed") {
+          if (
+            currentProgress.status === "completed" ||
+            currentProgress.status === "budget_exhausted"
+          ) {
             setState((prev) => ({
               ...prev,
               isGenerating: false,
               currentStep: "export",
               success: "Dataset generation completed!",
             }));
+          } else if (
+            currentProgress.status === "cancelled" ||
+            currentProgress.status.startsWith("error")
+          ) {
+            setState((prev) => ({
+              ...prev,
+              isGenerating: false,
+              error:
+                currentProgress.status === "cancelled"
+                  ? "Generation cancelled"
+                  : currentProgress.status,
+            }));
           }
         } catch (error) {
           setState((prev) => ({
@@ -71,6 +92,35 @@ export const useDatasetGenerator = () => {
     }
   }, [state.isGenerating]);
 
+  // Live preview of streamed entries, dropping those of batch attempts that failed
+  useEffect(() => {
+    if (!state.isGenerating) return;
+
+    const unlistenEntry = listen<GenerationEntryEvent>(
+      "generation-entry",
+      (event) => {
+        setState((prev) => ({
+          ...prev,
+          liveEntries: appendLiveEntry(prev.liveEntries, event.payload),
+        }));
+      }
+    );
+    const unlistenDiscarded = listen<GenerationBatchDiscardedEvent>(
+      "generation-batch-discarded",
+      (event) => {
+        setState((prev) => ({
+          ...prev,
+          liveEntries: discardLiveAttempt(prev.liveEntries, event.payload),
+        }));
+      }
+    );
+
+    return () => {
+      unlistenEntry.then((unlisten) => unlisten());
+      unlistenDiscarded.then((unlisten) => unlisten());
+    };
+  }, [state.isGenerating]);
+
   const discoverModels = useCallback(async () => {
     setState((prev) => ({
       ...prev,
@@ -112,6 +162,7 @@ export const useDatasetGenerator = () => {
     setState((prev) => ({
       ...prev,
       isGenerating: true,
+      liveEntries: [],
       currentStep: "generating",
       error: null,
       success: null,
@@ -243,6 +294,7 @@ export const useDatasetGenerator = () => {
       ...prev,
       currentStep: "models",
       progress: null,
+      liveEntries: [],
       isGenerating: false,
       error: null,
       success: null,
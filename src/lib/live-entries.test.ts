@@ -0,0 +1,32 @@
+import { describe, expect, it } from "vitest";
+import type { GenerationEntryEvent } from "@/types";
+import { appendLiveEntry, discardLiveAttempt, entryFields } from "./live-entries";
+
+// Payloads as the backend emits them: entry fields are flattened next to source_model
+const entryEvent = (attemptId: string, instruction: string): GenerationEntryEvent =>
+  JSON.parse(
+    `{"generation_id":"run","batch_id":0,"attempt_id":"${attemptId}",` +
+      `"entry":{"instruction":"${instruction}","output":"answer","source_model":"llama3"}}`
+  );
+
+describe("live entries", () => {
+  it("shows the fields of streamed entries", () => {
+    const liveEntries = appendLiveEntry([], entryEvent("first", "Explain tides"));
+
+    expect(entryFields(liveEntries[0].entry)).toEqual({
+      instruction: "Explain tides",
+      output: "answer",
+    });
+  });
+
+  it("drops the entries of a discarded attempt", () => {
+    let liveEntries = appendLiveEntry([], entryEvent("failed", "a"));
+    liveEntries = appendLiveEntry(liveEntries, entryEvent("retried", "b"));
+
+    liveEntries = discardLiveAttempt(
+      liveEntries,
+      JSON.parse('{"generation_id":"run","batch_id":0,"attempt_id":"failed"}')
+    );
+    expect(liveEntries.map((event) => event.attempt_id)).toEqual(["retried"]);
+  });
+});
@@ -0,0 +1,27 @@
+import type {
+  DatasetEntry,
+  GenerationBatchDiscardedEvent,
+  GenerationEntryEvent,
+} from "@/types";
+
+// Live preview state updates for the streamed generation events
+
+export function appendLiveEntry(
+  liveEntries: GenerationEntryEvent[],
+  event: GenerationEntryEvent
+): GenerationEntryEvent[] {
+  return [...liveEntries, event];
+}
+
+export function discardLiveAttempt(
+  liveEntries: GenerationEntryEvent[],
+  event: GenerationBatchDiscardedEvent
+): GenerationEntryEvent[] {
+  return liveEntries.filter((entry) => entry.attempt_id !== event.attempt_id);
+}
+
+// The training fields of an entry, without the model that generated it
+export function entryFields(entry: DatasetEntry): Record<string, any> {
+  const { source_model, ...fields } = entry;
+  return fields;
+}
@@ -3,8 +3,27 @@ export interface Model {
   name: string;
   size: string;
   modified: string;
-  provider: "Ollama" | "OpenAI";
+  provider: "Ollama" | "OpenAI" | "OpenAICompatible" | "Anthropic";
   capabilities: string[];
+  endpoint?: string;
+  details: ModelDetails;
+}
+
+export interface ModelDetails {
+  size_bytes?: number;
+  context_length?: number;
+  parameter_count?: number;
+  quantization?: string;
+  family?: string;
+  template?: string;
+}
+
+export interface OllamaConnectionStatus {
+  base_url: string;
+  reachable: boolean;
+  version?: string;
+  loaded_models: string[];
+  error?: string;
 }
 
 export type DatasetFormat =
@@ -37,6 +56,65 @@ export interface GenerationConfig {
   domain_context: string;
   selected_model?: string;
   format: DatasetFormat;
+  stream?: boolean;
+  budget?: GenerationBudget;
+  model_mix?: ModelWeight[];
+  fallback_models?: string[];
+  bypass_response_cache?: boolean;
+  seed?: number | null;
+  sampling?: SamplingConfig;
+}
+
+export interface SamplingConfig {
+  temperature?: number | null;
+  top_p?: number | null;
+  top_k?: number | null;
+  repeat_penalty?: number | null;
+  max_tokens?: number | null;
+  stop?: string[];
+  presence_penalty?: number | null;
+  frequency_penalty?: number | null;
+  temperature_schedule?: TemperatureSchedule | null;
+}
+
+export type TemperatureSchedule =
+  | { type: "linear"; start: number; end: number }
+  | { type: "per_batch"; temperatures: number[] };
+
+export interface CacheStats {
+  hits: number;
+  misses: number;
+  hit_rate: number;
+  entries: number;
+  size_bytes: number;
+}
+
+export interface ModelWeight {
+  model_id: string;
+  weight: number;
+}
+
+export interface GenerationBudget {
+  max_total_tokens?: number | null;
+  max_cost_usd?: number | null;
+  max_duration_secs?: number | null;
+}
+
+export type BudgetLimit = "tokens" | "cost" | "time";
+
+// Streamed entries are provisional until the run completes
+export interface GenerationEntryEvent {
+  generation_id: string;
+  batch_id: number;
+  attempt_id: string;
+  entry: DatasetEntry;
+}
+
+// Withdraws the entries streamed by a failed or retried batch attempt
+export interface GenerationBatchDiscardedEvent {
+  generation_id: string;
+  batch_id: number;
+  attempt_id: string;
 }
 
 export interface GenerationProgress {
@@ -50,12 +128,114 @@ export interface GenerationProgress {
   entries_per_second: number;
   errors_count: number;
   retries_count: number;
+  parse_success_rate: number;
+  batch_parse_success_rates: Record<number, number>;
+  usage: UsageSummary;
+  budget_exhausted: BudgetLimit | null;
+  model_substitutions: Record<number, ModelSubstitution>;
+  system_fingerprints: string[];
+  error_classes: Partial<Record<ErrorClass, number>>;
+  quarantined_responses: number;
+}
+
+export type ErrorClass =
+  | "timeout"
+  | "rate_limited"
+  | "server_error"
+  | "parse_failure"
+  | "network"
+  | "auth"
+  | "model_not_found"
+  | "invalid_request"
+  | "other";
+
+export interface QuarantinedResponse {
+  id: string;
+  generation_id: string;
+  batch_id: number;
+  model_id: string;
+  provider: Model["provider"];
+  endpoint: string | null;
+  expected_entries: number;
+  raw_output: string;
+  error: string;
+  quarantined_at: string;
+  request: Record<string, unknown>;
+}
+
+export interface ArchiveSettings {
+  enabled: boolean;
+  max_runs: number;
+  redact: boolean;
+}
+
+export interface ArchivedCall {
+  generation_id: string;
+  task_id: string;
+  batch_id: number;
+  provider: Model["provider"];
+  model: string;
+  started_at: string;
+  latency_ms: number;
+  status: "success" | "error";
+  error: string | null;
+  error_class: ErrorClass | null;
+  request: Record<string, unknown>;
+  response_text: string | null;
+  finish_reason: string | null;
+  usage: TokenUsage | null;
+  rate_limit_headers: Record<string, unknown> | null;
+}
+
+export interface ModelSubstitution {
+  original_model: string;
+  model: string;
+  provider: Model["provider"];
+  reason: string;
+}
+
+export interface TokenUsage {
+  prompt_tokens: number;
+  completion_tokens: number;
+}
+
+export interface ModelUsage {
+  requests: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  estimated_cost_usd: number | null;
+}
+
+export interface UsageSummary {
+  prompt_tokens: number;
+  completion_tokens: number;
+  estimated_cost_usd: number | null;
+  by_model: Record<string, ModelUsage>;
+  by_batch: Record<number, TokenUsage>;
+  unpriced_models: string[];
 }
 
-export interface DatasetEntry {
-  data: Record<string, any>;
+export interface ModelPricing {
+  prompt_per_million: number;
+  completion_per_million: number;
 }
 
+export interface RunSummary {
+  generation_id: string;
+  model: string;
+  status: string;
+  entries_generated: number;
+  duration_secs: number;
+  usage: UsageSummary;
+  seed: number | null;
+  system_fingerprints: string[];
+}
+
+// The format's fields sit at the top level, next to the generating model
+export type DatasetEntry = Record<string, any> & {
+  source_model?: string;
+};
+
 export type Step = "models" | "configuration" | "generating" | "export";
 
 export interface StepConfig {
@@ -71,6 +251,7 @@ export interface AppState {
   isDiscovering: boolean;
   isGenerating: boolean;
   progress: GenerationProgress | null;
+  liveEntries: GenerationEntryEvent[];
   currentStep: Step;
   generationConfig: GenerationConfig;
   error: string | null;